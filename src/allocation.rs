@@ -0,0 +1,496 @@
+use std::collections::BTreeMap;
+
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+
+use crate::tree::{AscendancyInfo, Coord, NodeKind, NodeMeta};
+use crate::{Error, Result};
+
+/// Cluster jewel node hashes are stored with this offset subtracted.
+const CLUSTER_NODE_OFFSET: u32 = 65536;
+/// Version of the tree URL format written by [`Allocation::encode`].
+const URL_VERSION: u32 = 6;
+const URL_PREFIX: &str = "https://www.pathofexile.com/passive-skill-tree/";
+
+pub(crate) const URL_SAFE: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Allocated passives of a single build, as encoded in an official passive tree URL.
+///
+/// Node ids match the ids of [`crate::tree::Node`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    /// Version of the tree URL format the allocation was decoded from,
+    /// `0` if the allocation does not originate from a tree URL.
+    pub version: u32,
+    pub class: u8,
+    pub ascendancy: u8,
+    pub alternate_ascendancy: u8,
+    pub nodes: Vec<u16>,
+    /// Cluster jewel node hashes, including the `65536` offset.
+    pub cluster_nodes: Vec<u32>,
+    /// Selected mastery effects, keyed by mastery node id.
+    pub mastery_effects: BTreeMap<u16, u16>,
+    /// Socketed jewels, keyed by jewel socket node id.
+    ///
+    /// Jewels are not part of tree URLs and are only known for imported builds.
+    pub jewels: BTreeMap<u32, Jewel>,
+}
+
+/// A jewel socketed into the passive tree.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Jewel {
+    /// Base type of the jewel, e.g. `Large Cluster Jewel`.
+    pub base_type: String,
+    /// All modifiers of the jewel, including enchantments and implicits.
+    pub mods: Vec<String>,
+    /// Nodes added by a cluster jewel, as exported by the official character window.
+    ///
+    /// Empty for other sources, their cluster nodes are generated from the modifiers
+    /// instead, see [`crate::cluster::expand`].
+    pub nodes: Vec<JewelNode>,
+}
+
+/// A node added to the tree by a cluster jewel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JewelNode {
+    /// Node hash, including the `65536` offset for cluster nodes.
+    pub id: u32,
+    pub kind: NodeKind,
+    pub meta: NodeMeta,
+    /// Center of the node's group, the node is placed on `orbit` around it.
+    pub group: Coord,
+    pub orbit: usize,
+    pub orbit_index: usize,
+    /// Connected nodes, either nodes of the same jewel or the socket of the jewel.
+    pub connections: Vec<u32>,
+}
+
+impl Allocation {
+    /// Creates an empty allocation for the class and ascendancy of `info`.
+    pub fn for_ascendancy(info: &AscendancyInfo) -> Self {
+        Self {
+            class: info.class,
+            ascendancy: info.ascendancy,
+            ..Default::default()
+        }
+    }
+
+    /// Selects the alternate ascendancy of `info`, the class is left unchanged.
+    pub fn set_alternate_ascendancy(&mut self, info: &AscendancyInfo) {
+        self.alternate_ascendancy = info.ascendancy;
+    }
+
+    /// Decodes a passive tree URL, e.g. `https://www.pathofexile.com/passive-skill-tree/AAAABgMA...`.
+    ///
+    /// Accepts full URLs, URLs with a tree version segment and query string,
+    /// as well as the bare base64 payload.
+    pub fn from_url(url: &str) -> Result<Self> {
+        let url = url.trim();
+        let url = url.split(['?', '#']).next().unwrap_or(url);
+        let payload = url.trim_end_matches('/').rsplit('/').next().unwrap_or(url);
+
+        let data = URL_SAFE
+            .decode(payload)
+            .map_err(|_| invalid(format!("tree url payload '{payload}' is not valid base64")))?;
+
+        Self::decode(&data)
+    }
+
+    /// Decodes the binary payload of a passive tree URL.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, offset: 0 };
+
+        let version = reader
+            .u32()
+            .ok_or_else(|| invalid("missing tree url version"))?;
+        if !(4..=6).contains(&version) {
+            return Err(invalid(format!(
+                "unsupported tree url version {version}, expected version 4 to 6"
+            )));
+        }
+
+        let class = reader.u8().ok_or_else(|| invalid("missing class"))?;
+        let ascendancies = reader.u8().ok_or_else(|| invalid("missing ascendancy"))?;
+        let (ascendancy, alternate_ascendancy) = match version {
+            6 => (ascendancies & 0b11, ascendancies >> 2),
+            _ => (ascendancies, 0),
+        };
+
+        let mut allocation = Allocation {
+            version,
+            class,
+            ascendancy,
+            alternate_ascendancy,
+            ..Default::default()
+        };
+
+        if version == 4 {
+            // Fullscreen flag, followed by nodes until the end of the payload.
+            reader
+                .u8()
+                .ok_or_else(|| invalid("missing fullscreen flag"))?;
+            while reader.remaining() > 0 {
+                allocation
+                    .nodes
+                    .push(reader.u16().ok_or_else(|| invalid("truncated node list"))?);
+            }
+            return Ok(allocation);
+        }
+
+        let count = reader.u8().ok_or_else(|| invalid("missing node count"))?;
+        for i in 0..count {
+            let node = reader
+                .u16()
+                .ok_or_else(|| invalid(format!("truncated node list at node {i} of {count}")))?;
+            allocation.nodes.push(node);
+        }
+
+        let count = reader
+            .u8()
+            .ok_or_else(|| invalid("missing cluster node count"))?;
+        for i in 0..count {
+            let node = reader.u16().ok_or_else(|| {
+                invalid(format!(
+                    "truncated cluster node list at node {i} of {count}"
+                ))
+            })?;
+            allocation
+                .cluster_nodes
+                .push(u32::from(node) + CLUSTER_NODE_OFFSET);
+        }
+
+        let count = reader
+            .u8()
+            .ok_or_else(|| invalid("missing mastery effect count"))?;
+        for i in 0..count {
+            let effect = reader
+                .u16()
+                .ok_or_else(|| invalid(format!("truncated mastery effect {i} of {count}")))?;
+            let node = reader
+                .u16()
+                .ok_or_else(|| invalid(format!("truncated mastery effect {i} of {count}")))?;
+            allocation.mastery_effects.insert(node, effect);
+        }
+
+        if reader.remaining() > 0 {
+            return Err(invalid(format!(
+                "unexpected {} trailing bytes after mastery effects",
+                reader.remaining()
+            )));
+        }
+
+        Ok(allocation)
+    }
+
+    /// Encodes the allocation as an official passive tree URL.
+    pub fn to_url(&self) -> Result<String> {
+        let data = self.encode()?;
+        Ok(format!("{URL_PREFIX}{}", URL_SAFE.encode(data)))
+    }
+
+    /// Encodes the allocation into the binary payload of a passive tree URL.
+    ///
+    /// The payload is always written in the latest supported format (version 6),
+    /// regardless of the version the allocation was decoded from.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.ascendancy > 0b11 {
+            return Err(Error::Encode(format!(
+                "ascendancy {} does not fit into the tree url",
+                self.ascendancy
+            )));
+        }
+        if self.alternate_ascendancy > 0b11_1111 {
+            return Err(Error::Encode(format!(
+                "alternate ascendancy {} does not fit into the tree url",
+                self.alternate_ascendancy
+            )));
+        }
+
+        let mut data = Vec::with_capacity(
+            10 + self.nodes.len() * 2
+                + self.cluster_nodes.len() * 2
+                + self.mastery_effects.len() * 4,
+        );
+        data.extend(URL_VERSION.to_be_bytes());
+        data.push(self.class);
+        data.push(self.alternate_ascendancy << 2 | self.ascendancy);
+
+        data.push(count("nodes", self.nodes.len())?);
+        for node in &self.nodes {
+            data.extend(node.to_be_bytes());
+        }
+
+        data.push(count("cluster nodes", self.cluster_nodes.len())?);
+        for &node in &self.cluster_nodes {
+            let node = node
+                .checked_sub(CLUSTER_NODE_OFFSET)
+                .and_then(|node| u16::try_from(node).ok())
+                .ok_or_else(|| Error::Encode(format!("{node} is not a valid cluster node hash")))?;
+            data.extend(node.to_be_bytes());
+        }
+
+        data.push(count("mastery effects", self.mastery_effects.len())?);
+        for (node, effect) in &self.mastery_effects {
+            data.extend(effect.to_be_bytes());
+            data.extend(node.to_be_bytes());
+        }
+
+        Ok(data)
+    }
+}
+
+fn count(what: &str, len: usize) -> Result<u8> {
+    u8::try_from(len).map_err(|_| {
+        Error::Encode(format!(
+            "too many {what} ({len}) for a tree url, max is 255"
+        ))
+    })
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::Decode(message.into())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.data.get(self.offset..self.offset + N)?;
+        self.offset += N;
+        Some(bytes.try_into().expect("slice has exactly N bytes"))
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[b]| b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_be_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+    use crate::tree::{self, Ascendancy};
+
+    fn v4() -> Vec<u8> {
+        let mut data = vec![0, 0, 0, 4, 3, 1, 0];
+        data.extend([0x01, 0x02, 0xAB, 0xCD]);
+        data
+    }
+
+    fn v5() -> Vec<u8> {
+        let mut data = vec![0, 0, 0, 5, 2, 2];
+        data.extend([2, 0x01, 0x02, 0xAB, 0xCD]);
+        data.extend([1, 0x00, 0x10]);
+        data.extend([1, 0x50, 0x01, 0x00, 0x30]);
+        data
+    }
+
+    fn v6() -> Vec<u8> {
+        let mut data = vec![0, 0, 0, 6, 4, 0b1001];
+        data.extend([1, 0x01, 0x02]);
+        data.extend([2, 0x00, 0x10, 0x01, 0x00]);
+        data.extend([2, 0x50, 0x01, 0x00, 0x30, 0x50, 0x02, 0x00, 0x40]);
+        data
+    }
+
+    #[test]
+    fn decode_v4() {
+        let allocation = Allocation::decode(&v4()).unwrap();
+
+        assert_eq!(allocation.version, 4);
+        assert_eq!(allocation.class, 3);
+        assert_eq!(allocation.ascendancy, 1);
+        assert_eq!(allocation.alternate_ascendancy, 0);
+        assert_eq!(allocation.nodes, [0x0102, 0xABCD]);
+        assert!(allocation.cluster_nodes.is_empty());
+        assert!(allocation.mastery_effects.is_empty());
+    }
+
+    #[test]
+    fn decode_v5() {
+        let allocation = Allocation::decode(&v5()).unwrap();
+
+        assert_eq!(allocation.version, 5);
+        assert_eq!(allocation.class, 2);
+        assert_eq!(allocation.ascendancy, 2);
+        assert_eq!(allocation.alternate_ascendancy, 0);
+        assert_eq!(allocation.nodes, [0x0102, 0xABCD]);
+        assert_eq!(allocation.cluster_nodes, [0x10 + CLUSTER_NODE_OFFSET]);
+        assert_eq!(allocation.mastery_effects, BTreeMap::from([(0x30, 0x5001)]));
+    }
+
+    #[test]
+    fn decode_v6() {
+        let allocation = Allocation::decode(&v6()).unwrap();
+
+        assert_eq!(allocation.version, 6);
+        assert_eq!(allocation.class, 4);
+        assert_eq!(allocation.ascendancy, 1);
+        assert_eq!(allocation.alternate_ascendancy, 2);
+        assert_eq!(allocation.nodes, [0x0102]);
+        assert_eq!(
+            allocation.cluster_nodes,
+            [0x10 + CLUSTER_NODE_OFFSET, 0x100 + CLUSTER_NODE_OFFSET]
+        );
+        assert_eq!(
+            allocation.mastery_effects,
+            BTreeMap::from([(0x30, 0x5001), (0x40, 0x5002)])
+        );
+    }
+
+    #[test]
+    fn from_url() {
+        let payload = URL_SAFE.encode(v6());
+        let expected = Allocation::decode(&v6()).unwrap();
+
+        for url in [
+            payload.clone(),
+            format!("{URL_PREFIX}{payload}"),
+            format!("{URL_PREFIX}3.25/{payload}?accountName=foo"),
+            format!("  {payload}/  "),
+        ] {
+            assert_eq!(Allocation::from_url(&url).unwrap(), expected, "{url}");
+        }
+    }
+
+    #[test]
+    fn decode_malformed() {
+        let truncated = |data: Vec<u8>| data[..data.len() - 1].to_vec();
+        let mut trailing = v6();
+        trailing.push(0);
+
+        for (data, error) in [
+            (vec![], "missing tree url version"),
+            (vec![0, 0, 0, 3, 0, 0], "unsupported tree url version 3"),
+            (vec![0, 0, 0, 7, 0, 0], "unsupported tree url version 7"),
+            (vec![0, 0, 0, 6], "missing class"),
+            (vec![0, 0, 0, 6, 1], "missing ascendancy"),
+            (vec![0, 0, 0, 4, 1, 0], "missing fullscreen flag"),
+            (truncated(v4()), "truncated node list"),
+            (vec![0, 0, 0, 6, 1, 0], "missing node count"),
+            (vec![0, 0, 0, 6, 1, 0, 2, 0, 1], "truncated node list"),
+            (vec![0, 0, 0, 6, 1, 0, 0], "missing cluster node count"),
+            (vec![0, 0, 0, 6, 1, 0, 0, 1], "truncated cluster node list"),
+            (vec![0, 0, 0, 6, 1, 0, 0, 0], "missing mastery effect count"),
+            (truncated(v5()), "truncated mastery effect"),
+            (trailing, "unexpected 1 trailing bytes"),
+        ] {
+            let err = Allocation::decode(&data).unwrap_err();
+            assert!(
+                format!("{err:#}").contains(error),
+                "{data:?}: expected '{error}', got '{err:#}'"
+            );
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        for data in [v4(), v5(), v6()] {
+            let allocation = Allocation::decode(&data).unwrap();
+            let encoded = allocation.encode().unwrap();
+            let decoded = Allocation::decode(&encoded).unwrap();
+
+            assert_eq!(decoded.version, URL_VERSION);
+            assert_eq!(
+                decoded,
+                Allocation {
+                    version: URL_VERSION,
+                    ..allocation
+                }
+            );
+        }
+
+        // Version 6 payloads are written back unchanged.
+        assert_eq!(Allocation::decode(&v6()).unwrap().encode().unwrap(), v6());
+    }
+
+    #[test]
+    fn roundtrip_url() {
+        let allocation = Allocation::decode(&v6()).unwrap();
+        let url = allocation.to_url().unwrap();
+
+        assert!(url.starts_with(URL_PREFIX), "{url}");
+        assert_eq!(Allocation::from_url(&url).unwrap(), allocation);
+    }
+
+    #[test]
+    fn encode_invalid() {
+        let allocation = Allocation {
+            ascendancy: 4,
+            ..Default::default()
+        };
+        assert!(allocation.encode().is_err());
+
+        let allocation = Allocation {
+            cluster_nodes: vec![100],
+            ..Default::default()
+        };
+        assert!(allocation.encode().is_err());
+
+        let allocation = Allocation {
+            nodes: vec![1; 256],
+            ..Default::default()
+        };
+        assert!(allocation.encode().is_err());
+    }
+
+    #[test]
+    fn for_ascendancy() {
+        let data = data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap();
+        let tree = tree::build(&data, &Default::default()).unwrap();
+
+        let berserker = tree.ascendancies[&Ascendancy::new("Berserker")];
+        assert_eq!((berserker.class, berserker.ascendancy), (1, 2));
+
+        let allocation = Allocation::for_ascendancy(&berserker);
+        let allocation = Allocation::decode(&allocation.encode().unwrap()).unwrap();
+        assert_eq!(
+            tree.ascendancy(allocation.class, allocation.ascendancy),
+            Some(Ascendancy::new("Berserker"))
+        );
+        assert_eq!(allocation.alternate_ascendancy, 0);
+
+        let (_, warlock) = tree
+            .alternate_ascendancies
+            .iter()
+            .find(|(name, info)| name.as_ref() == "Warlock" && info.class == berserker.class)
+            .unwrap();
+        assert_eq!(warlock.ascendancy, 2);
+
+        let mut allocation = Allocation::for_ascendancy(&berserker);
+        allocation.set_alternate_ascendancy(warlock);
+        let allocation = Allocation::decode(&allocation.encode().unwrap()).unwrap();
+        assert_eq!(
+            tree.ascendancy(allocation.class, allocation.ascendancy),
+            Some(Ascendancy::new("Berserker"))
+        );
+        assert_eq!(
+            tree.alternate_ascendancy(allocation.class, allocation.alternate_ascendancy),
+            Some(Ascendancy::new("Warlock"))
+        );
+    }
+
+    #[test]
+    fn from_url_invalid_base64() {
+        let err = Allocation::from_url("AAAA!AAA").unwrap_err();
+        assert!(err.to_string().contains("not valid base64"), "{err}");
+    }
+}
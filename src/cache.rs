@@ -0,0 +1,177 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use sha2::{Digest, Sha256};
+
+/// Content cache for downloaded tree data.
+///
+/// Data with a known checksum is stored under its checksum, everything else
+/// under the checksum of the URL it was downloaded from.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: Option<PathBuf>,
+    offline: bool,
+}
+
+impl Cache {
+    /// Creates a new cache in `dir`, without a directory nothing is cached.
+    ///
+    /// In `offline` mode data is only read from the cache and never downloaded.
+    pub fn new(dir: Option<PathBuf>, offline: bool) -> Self {
+        Self { dir, offline }
+    }
+
+    /// Reads `url` from the cache or downloads and caches it.
+    ///
+    /// With a `sha256` checksum the data is verified, mismatching data is never cached.
+    pub fn fetch(&self, url: &str, sha256: Option<&str>) -> anyhow::Result<String> {
+        let key = match sha256 {
+            Some(sha256) => sha256.to_ascii_lowercase(),
+            None => hex_sha256(url.as_bytes()),
+        };
+        let path = self.dir.as_ref().map(|dir| dir.join(format!("{key}.json")));
+
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            let data = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read cached {}", path.display()))?;
+            if let Some(sha256) = sha256 {
+                verify(&data, sha256)
+                    .with_context(|| format!("cached {} is corrupted", path.display()))?;
+            }
+            return Ok(data);
+        }
+
+        if self.offline {
+            match path {
+                Some(_) => bail!("{url} is not cached and cannot be downloaded in offline mode"),
+                None => bail!("offline mode requires a cache directory"),
+            }
+        }
+
+        let data = ureq::get(url).call()?.into_string()?;
+        if let Some(sha256) = sha256 {
+            verify(&data, sha256).with_context(|| format!("failed to verify {url}"))?;
+        }
+
+        if let Some(path) = path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            write_atomic(&path, &data)
+                .with_context(|| format!("failed to cache {}", path.display()))?;
+        }
+
+        Ok(data)
+    }
+}
+
+/// Writes `data` to a temporary file next to `path` and moves it into place,
+/// concurrent readers never see a partially written file.
+fn write_atomic(path: &Path, data: &str) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
+/// Makes sure `data` has the checksum `sha256`.
+pub fn verify(data: &str, sha256: &str) -> anyhow::Result<()> {
+    let actual = hex_sha256(data.as_bytes());
+    if !actual.eq_ignore_ascii_case(sha256) {
+        bail!("checksum mismatch, expected sha256 {sha256} but got {actual}");
+    }
+    Ok(())
+}
+
+pub(crate) fn hex_sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.invalid/data.json";
+    const DATA: &str = "{}";
+
+    /// Cache in a fresh temporary directory, pre-populated with `files`.
+    fn cache(name: &str, files: &[(&str, &str)]) -> (Cache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("tmm-cache-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, data) in files {
+            std::fs::write(dir.join(file), data).unwrap();
+        }
+        (Cache::new(Some(dir.clone()), true), dir)
+    }
+
+    #[test]
+    fn hit() {
+        let sha256 = hex_sha256(DATA.as_bytes());
+        let (cache, dir) = cache("hit", &[(&format!("{sha256}.json"), DATA)]);
+
+        assert_eq!(cache.fetch(URL, Some(&sha256)).unwrap(), DATA);
+        assert_eq!(
+            cache.fetch(URL, Some(&sha256.to_uppercase())).unwrap(),
+            DATA
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hit_without_checksum() {
+        let key = hex_sha256(URL.as_bytes());
+        let (cache, dir) = cache("url", &[(&format!("{key}.json"), DATA)]);
+
+        assert_eq!(cache.fetch(URL, None).unwrap(), DATA);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn offline_miss() {
+        let (cache, dir) = cache("miss", &[]);
+
+        let err = cache.fetch(URL, None).unwrap_err();
+        assert!(err.to_string().contains("not cached"), "{err}");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let sha256 = hex_sha256(DATA.as_bytes());
+        let (cache, dir) = cache("mismatch", &[(&format!("{sha256}.json"), "[]")]);
+
+        let err = cache.fetch(URL, Some(&sha256)).unwrap_err();
+        assert!(err.to_string().contains("is corrupted"), "{err}");
+        assert!(format!("{err:#}").contains("checksum mismatch"), "{err:#}");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn atomic_write() {
+        let (_, dir) = cache("write", &[]);
+        let path = dir.join("data.json");
+
+        write_atomic(&path, DATA).unwrap();
+        write_atomic(&path, "[]").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::allocation::{Allocation, Jewel, JewelNode};
+use crate::data;
+use crate::tree::{Coord, NodeKind, NodeMeta};
+use crate::{Error, Result};
+
+/// Cluster jewel node hashes in `hashes_ex` are stored with this offset subtracted.
+const CLUSTER_NODE_OFFSET: u32 = 65536;
+
+/// Response of the character window `get-passive-skills` endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Passives {
+    #[serde(default)]
+    pub character: u8,
+    #[serde(default)]
+    pub ascendancy: u8,
+    #[serde(default)]
+    pub alternate_ascendancy: u8,
+    #[serde(default)]
+    pub hashes: Vec<u16>,
+    /// Allocated cluster jewel nodes, without the cluster node offset.
+    #[serde(default)]
+    pub hashes_ex: Vec<u32>,
+    /// Selected mastery effects, keyed by mastery node id.
+    #[serde(default, deserialize_with = "map")]
+    pub mastery_effects: BTreeMap<String, MasteryEffect>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    /// Expansion data of socketed cluster jewels, keyed by the socket index of the item.
+    #[serde(default, deserialize_with = "map")]
+    pub jewel_data: BTreeMap<String, JewelData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MasteryEffect {
+    Number(u32),
+    String(String),
+}
+
+/// A socketed jewel.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    /// Index of the socket in the tree's jewel slots.
+    pub x: usize,
+    #[serde(default)]
+    pub base_type: Option<String>,
+    #[serde(default)]
+    pub type_line: String,
+    #[serde(default)]
+    pub enchant_mods: Vec<String>,
+    #[serde(default)]
+    pub implicit_mods: Vec<String>,
+    #[serde(default)]
+    pub explicit_mods: Vec<String>,
+    #[serde(default)]
+    pub crafted_mods: Vec<String>,
+}
+
+/// Expansion data of a socketed jewel.
+#[derive(Debug, Deserialize)]
+pub struct JewelData {
+    /// Nodes added by a cluster jewel, missing for other jewels.
+    pub subgraph: Option<Subgraph>,
+}
+
+/// Groups and nodes added by a cluster jewel, in the format of the tree data.
+#[derive(Debug, Deserialize)]
+pub struct Subgraph {
+    #[serde(default, deserialize_with = "map")]
+    pub groups: BTreeMap<String, SubgraphGroup>,
+    #[serde(default, deserialize_with = "map")]
+    pub nodes: BTreeMap<String, SubgraphNode>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubgraphGroup {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphNode {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub stats: Vec<String>,
+    pub group: String,
+    pub orbit: usize,
+    #[serde(default)]
+    pub orbit_index: usize,
+    #[serde(default)]
+    pub out: Vec<String>,
+    #[serde(default, rename = "in")]
+    pub in_: Vec<String>,
+    #[serde(default)]
+    pub is_notable: bool,
+    #[serde(default)]
+    pub is_keystone: bool,
+    #[serde(default)]
+    pub is_mastery: bool,
+    #[serde(default)]
+    pub is_jewel_socket: bool,
+}
+
+impl Passives {
+    pub fn new(s: &str) -> Result<Self> {
+        serde_json::from_str(s)
+            .map_err(|err| Error::Decode(format!("invalid passive skills json: {err}")))
+    }
+
+    /// Converts the passives into an [`Allocation`].
+    ///
+    /// The tree is required to resolve the socket indices of jewels.
+    pub fn allocation(&self, tree: &data::Tree) -> Result<Allocation> {
+        let mut allocation = Allocation {
+            class: self.character,
+            ascendancy: self.ascendancy,
+            alternate_ascendancy: self.alternate_ascendancy,
+            nodes: self.hashes.clone(),
+            cluster_nodes: self
+                .hashes_ex
+                .iter()
+                .map(|hash| hash + CLUSTER_NODE_OFFSET)
+                .collect(),
+            ..Default::default()
+        };
+
+        for (node, effect) in &self.mastery_effects {
+            let node = node
+                .parse()
+                .map_err(|_| Error::Decode(format!("invalid mastery node id '{node}'")))?;
+            let effect = match effect {
+                MasteryEffect::Number(effect) => *effect,
+                MasteryEffect::String(effect) => effect
+                    .parse()
+                    .map_err(|_| Error::Decode(format!("invalid mastery effect '{effect}'")))?,
+            };
+
+            let Ok(effect) = u16::try_from(effect) else {
+                return Err(Error::Decode(format!(
+                    "mastery effect {effect} of mastery node {node} is out of range"
+                )));
+            };
+            allocation.mastery_effects.insert(node, effect);
+        }
+
+        for item in &self.items {
+            let node = tree.jewel_slots().get(item.x).ok_or_else(|| {
+                Error::Decode(format!("jewel socket index {} does not exist", item.x))
+            })?;
+
+            let mods = [
+                &item.enchant_mods,
+                &item.implicit_mods,
+                &item.explicit_mods,
+                &item.crafted_mods,
+            ];
+            let nodes = match self
+                .jewel_data
+                .get(&item.x.to_string())
+                .and_then(|data| data.subgraph.as_ref())
+            {
+                Some(subgraph) => subgraph.nodes().map_err(|err| {
+                    Error::Decode(format!("invalid jewel data of socket {node}: {err}"))
+                })?,
+                None => Vec::new(),
+            };
+            let jewel = Jewel {
+                base_type: item.base_type.as_ref().unwrap_or(&item.type_line).clone(),
+                mods: mods.into_iter().flatten().cloned().collect(),
+                nodes,
+            };
+
+            allocation.jewels.insert(u32::from(*node), jewel);
+        }
+
+        Ok(allocation)
+    }
+}
+
+impl Subgraph {
+    fn nodes(&self) -> Result<Vec<JewelNode>> {
+        self.nodes
+            .iter()
+            .map(|(id, node)| {
+                let group = self.groups.get(&node.group).ok_or_else(|| {
+                    Error::Decode(format!("node {id} references unknown group {}", node.group))
+                })?;
+
+                let kind = if node.is_keystone {
+                    NodeKind::Keystone
+                } else if node.is_mastery {
+                    NodeKind::Mastery
+                } else if node.is_notable {
+                    NodeKind::Notable
+                } else if node.is_jewel_socket {
+                    NodeKind::Jewel
+                } else {
+                    NodeKind::Normal
+                };
+
+                let connections = node
+                    .out
+                    .iter()
+                    .chain(&node.in_)
+                    .map(|id| parse_node_id(id))
+                    .collect::<Result<_>>()?;
+
+                Ok(JewelNode {
+                    id: parse_node_id(id)?,
+                    kind,
+                    meta: NodeMeta {
+                        name: node.name.clone(),
+                        stats: node.stats.clone(),
+                    },
+                    group: Coord {
+                        x: group.x as i32,
+                        y: group.y as i32,
+                    },
+                    orbit: node.orbit,
+                    orbit_index: node.orbit_index,
+                    connections,
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse_node_id(id: &str) -> Result<u32> {
+    id.parse()
+        .map_err(|_| Error::Decode(format!("invalid node id '{id}'")))
+}
+
+/// Empty maps are exported as empty lists.
+fn map<'de, D, T>(deserializer: D) -> Result<BTreeMap<String, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MapOrList<T> {
+        Map(BTreeMap<String, T>),
+        List([(); 0]),
+    }
+
+    Ok(match MapOrList::deserialize(deserializer)? {
+        MapOrList::Map(map) => map,
+        MapOrList::List(_) => BTreeMap::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::{self, Coord};
+    use crate::{cluster, data};
+
+    fn tree_data() -> data::Tree {
+        data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap()
+    }
+
+    #[test]
+    fn passives() {
+        let passives = Passives::new(include_str!("../tests/fixtures/passives.json")).unwrap();
+        let allocation = passives.allocation(&tree_data()).unwrap();
+
+        assert_eq!(allocation.class, 1);
+        assert_eq!(allocation.ascendancy, 2);
+        assert_eq!(allocation.alternate_ascendancy, 0);
+        assert_eq!(allocation.nodes, [101, 102, 105]);
+        assert!(allocation.cluster_nodes.is_empty());
+        assert_eq!(allocation.mastery_effects, BTreeMap::from([(105, 48385)]));
+        assert!(allocation.jewels.is_empty());
+    }
+
+    #[test]
+    fn passives_cluster_jewel() {
+        let data = tree_data();
+        let passives =
+            Passives::new(include_str!("../tests/fixtures/passives-cluster.json")).unwrap();
+        let allocation = passives.allocation(&data).unwrap();
+
+        assert_eq!(allocation.nodes, [101, 103]);
+        assert_eq!(allocation.cluster_nodes, [65568, 65570, 65572, 65573]);
+        assert!(allocation.mastery_effects.is_empty());
+
+        let jewel = &allocation.jewels[&103];
+        assert_eq!(jewel.base_type, "Large Cluster Jewel");
+        assert!(jewel
+            .mods
+            .contains(&"1 Added Passive Skill is Burning Bright".to_owned()));
+        assert_eq!(jewel.nodes.len(), 8);
+
+        let notable = jewel.nodes.iter().find(|node| node.id == 65572).unwrap();
+        assert_eq!(notable.kind, NodeKind::Notable);
+        assert_eq!(notable.meta.name, "Burning Bright");
+        assert_eq!(notable.meta.stats.len(), 2);
+        assert_eq!(notable.group, Coord { x: 3000, y: 0 });
+        assert_eq!((notable.orbit, notable.orbit_index), (3, 7));
+        assert_eq!(notable.connections, [65573, 65570]);
+
+        let mut tree = tree::build(&data, &Default::default()).unwrap();
+        cluster::expand(&mut tree, &data, &allocation.jewels).unwrap();
+
+        let notable = tree.nodes.iter().find(|node| node.id == 65572).unwrap();
+        assert_eq!(notable.meta.name, "Burning Bright");
+        assert_eq!(notable.kind, NodeKind::Notable);
+        // The entrance of the cluster connects to the socket.
+        assert!(tree
+            .connections
+            .iter()
+            .any(|c| (c.a.id, c.b.id) == (65568, 103) || (c.a.id, c.b.id) == (103, 65568)));
+    }
+
+    #[test]
+    fn mastery_effect_out_of_range() {
+        let passives = Passives::new(r#"{"mastery_effects": {"105": 65536}}"#).unwrap();
+
+        let err = passives.allocation(&tree_data()).unwrap_err();
+        assert!(err.to_string().contains("mastery node 105"), "{err}");
+    }
+
+    #[test]
+    fn unknown_jewel_socket() {
+        let passives =
+            Passives::new(r#"{"items": [{"x": 1, "typeLine": "Cobalt Jewel"}]}"#).unwrap();
+
+        let err = passives.allocation(&tree_data()).unwrap_err();
+        assert!(err.to_string().contains("jewel socket index 1"), "{err}");
+    }
+}
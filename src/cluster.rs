@@ -0,0 +1,674 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::allocation::{Jewel, JewelNode};
+use crate::data;
+use crate::tree::{Connection, Coord, Node, NodeKind, NodeMeta, NodeRef, Path, Tree};
+use crate::{Error, Result};
+
+/// Cluster jewel node hashes start at this offset.
+const CLUSTER_NODE_OFFSET: u32 = 65536;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ClusterSize {
+    Small,
+    Medium,
+    Large,
+}
+
+/// Placement of cluster nodes on the orbit of a cluster, same as Path of Building.
+struct Layout {
+    max_nodes: usize,
+    small_indices: &'static [usize],
+    notable_indices: &'static [usize],
+    socket_indices: &'static [usize],
+    total_indices: usize,
+}
+
+impl ClusterSize {
+    /// Size index as used by `expansionJewel.size` and in the cluster node hashes.
+    fn index(self) -> u8 {
+        match self {
+            Self::Small => 0,
+            Self::Medium => 1,
+            Self::Large => 2,
+        }
+    }
+
+    fn layout(self) -> &'static Layout {
+        match self {
+            Self::Small => &Layout {
+                max_nodes: 3,
+                small_indices: &[0, 4, 2],
+                notable_indices: &[4],
+                socket_indices: &[4],
+                total_indices: 6,
+            },
+            Self::Medium => &Layout {
+                max_nodes: 6,
+                small_indices: &[0, 6, 8, 4, 10, 2],
+                notable_indices: &[6, 10, 2, 0],
+                socket_indices: &[6],
+                total_indices: 12,
+            },
+            Self::Large => &Layout {
+                max_nodes: 12,
+                small_indices: &[0, 4, 6, 8, 10, 2, 7, 5, 9, 3, 11, 1],
+                notable_indices: &[6, 4, 8, 10, 2],
+                socket_indices: &[4, 8, 6],
+                total_indices: 12,
+            },
+        }
+    }
+}
+
+/// A cluster jewel, parsed from the modifiers of a [`Jewel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterJewel {
+    pub size: ClusterSize,
+    pub node_count: usize,
+    pub socket_count: usize,
+    /// Names of the added notables.
+    pub notables: Vec<String>,
+    /// Stats granted by the added small passives.
+    pub small_stats: Vec<String>,
+}
+
+impl ClusterJewel {
+    /// Parses a cluster jewel, returns `None` if the jewel is not a cluster jewel.
+    pub fn from_jewel(jewel: &Jewel) -> Option<Self> {
+        let size = match jewel.base_type.as_str() {
+            "Small Cluster Jewel" => ClusterSize::Small,
+            "Medium Cluster Jewel" => ClusterSize::Medium,
+            "Large Cluster Jewel" => ClusterSize::Large,
+            _ => return None,
+        };
+
+        let mut cluster = Self {
+            size,
+            node_count: size.layout().max_nodes,
+            socket_count: 0,
+            notables: Vec::new(),
+            small_stats: Vec::new(),
+        };
+
+        for m in &jewel.mods {
+            if let Some(count) = m
+                .strip_prefix("Adds ")
+                .and_then(|m| m.strip_suffix(" Passive Skills"))
+            {
+                cluster.node_count = count.parse().unwrap_or(cluster.node_count);
+            } else if m == "1 Added Passive Skill is a Jewel Socket" {
+                cluster.socket_count = 1;
+            } else if let Some(count) = m.strip_suffix(" Added Passive Skills are Jewel Sockets") {
+                cluster.socket_count = count.parse().unwrap_or(0);
+            } else if let Some(notable) = m.strip_prefix("1 Added Passive Skill is ") {
+                cluster.notables.push(notable.to_owned());
+            } else if let Some(stat) = m
+                .strip_prefix("Added Small Passive Skills grant: ")
+                .or_else(|| m.strip_prefix("Added Small Passive Skills also grant: "))
+            {
+                cluster.small_stats.push(stat.to_owned());
+            }
+        }
+
+        cluster.node_count = cluster.node_count.min(size.layout().max_nodes);
+
+        Some(cluster)
+    }
+}
+
+/// Whether [`expand`] adds nodes for `jewel`, either exported nodes or a cluster
+/// jewel to generate them from.
+pub fn is_cluster(jewel: &Jewel) -> bool {
+    !jewel.nodes.is_empty() || ClusterJewel::from_jewel(jewel).is_some()
+}
+
+/// Adds the nodes and connections of all socketed cluster jewels to `tree`.
+///
+/// Jewels in sockets which do not exist in the tree, e.g. a socket of a missing
+/// parent cluster, and jewels which are not cluster jewels are ignored.
+pub fn expand(tree: &mut Tree, data: &data::Tree, jewels: &BTreeMap<u32, Jewel>) -> Result<()> {
+    let mut pending: Vec<_> = jewels
+        .iter()
+        .filter_map(|(&socket, jewel)| {
+            let socket = u16::try_from(socket).ok()?;
+            if !jewel.nodes.is_empty() {
+                return Some((socket, Pending::Exported(&jewel.nodes)));
+            }
+            Some((socket, Pending::Generated(ClusterJewel::from_jewel(jewel)?)))
+        })
+        .collect();
+
+    // Nested sockets only exist once their parent cluster has been added.
+    while let Some(i) = pending
+        .iter()
+        .position(|(socket, _)| tree.nodes.iter().any(|n| n.id == u32::from(*socket)))
+    {
+        let (socket, jewel) = pending.swap_remove(i);
+        match jewel {
+            Pending::Exported(nodes) => add_exported(tree, data, socket, nodes),
+            Pending::Generated(jewel) => add_cluster(tree, data, socket, &jewel),
+        }
+        .map_err(|err| match err {
+            Error::Cluster(message) => invalid(format!(
+                "failed to add cluster jewel in socket {socket}: {message}"
+            )),
+            err => err,
+        })?;
+    }
+
+    tree.nodes.sort();
+    tree.connections.sort();
+
+    Ok(())
+}
+
+/// Adds the cluster jewels of several builds to `tree`, e.g. to compare them.
+///
+/// Each build is expanded on its own. Cluster node ids only depend on the socket,
+/// if builds socket different jewels in the same socket the nodes of the first
+/// build are kept.
+pub fn expand_all(
+    tree: &mut Tree,
+    data: &data::Tree,
+    builds: &[&BTreeMap<u32, Jewel>],
+) -> Result<()> {
+    let base = tree.clone();
+
+    for jewels in builds {
+        let mut expanded = base.clone();
+        expand(&mut expanded, data, jewels)?;
+
+        let existing: BTreeSet<_> = tree.nodes.iter().map(|node| node.id).collect();
+        for node in expanded.nodes {
+            if !existing.contains(&node.id) {
+                tree.view_box.extend(node.position);
+                tree.nodes.push(node);
+            }
+        }
+
+        let existing: BTreeSet<_> = tree.connections.iter().cloned().collect();
+        tree.connections.extend(
+            expanded
+                .connections
+                .into_iter()
+                .filter(|connection| !existing.contains(connection)),
+        );
+    }
+
+    tree.nodes.sort();
+    tree.connections.sort();
+
+    Ok(())
+}
+
+enum Pending<'a> {
+    /// Nodes of the jewel are part of the export.
+    Exported(&'a [JewelNode]),
+    /// Nodes are generated from the modifiers of the jewel.
+    Generated(ClusterJewel),
+}
+
+/// Adds the exported nodes of a cluster jewel, see [`Jewel::nodes`].
+fn add_exported(
+    tree: &mut Tree,
+    data: &data::Tree,
+    socket: u16,
+    nodes: &[JewelNode],
+) -> Result<()> {
+    let mut positions = BTreeMap::new();
+    for node in nodes {
+        let (angle, x, y) = data
+            .orbit_position_at(
+                node.group.x as f32,
+                node.group.y as f32,
+                node.orbit,
+                node.orbit_index,
+            )
+            .ok_or_else(|| {
+                invalid(format!(
+                    "node {} is placed on slot {} of orbit {}, which does not exist",
+                    node.id, node.orbit_index, node.orbit
+                ))
+            })?;
+
+        let position = Coord { x, y };
+        tree.view_box.extend(position);
+        // Nested sockets can already be part of the tree.
+        if !tree.nodes.iter().any(|existing| existing.id == node.id) {
+            tree.nodes.push(Node {
+                id: node.id,
+                position,
+                kind: node.kind.clone(),
+                meta: node.meta.clone(),
+            });
+        }
+        positions.insert(node.id, (node, angle, position));
+    }
+
+    let mut connected = Vec::new();
+    for &(node, angle, position) in positions.values() {
+        let a = NodeRef {
+            id: node.id,
+            position,
+            kind: node.kind.clone(),
+        };
+
+        for &out in &node.connections {
+            let (b, path) = match positions.get(&out) {
+                Some(&(out_node, out_angle, out_position)) => {
+                    let b = NodeRef {
+                        id: out,
+                        position: out_position,
+                        kind: out_node.kind.clone(),
+                    };
+                    let path = match data.orbit_radius(node.orbit) {
+                        Some(radius)
+                            if node.group == out_node.group && node.orbit == out_node.orbit =>
+                        {
+                            Path::arc(radius, angle, out_angle)
+                        }
+                        _ => Path::Line {},
+                    };
+                    (b, path)
+                }
+                // Only the socket of the jewel is connected outside of the cluster.
+                None if out == u32::from(socket) => {
+                    let Some(socket) = tree.nodes.iter().find(|node| node.id == out) else {
+                        continue;
+                    };
+                    (NodeRef::from(socket), Path::Line {})
+                }
+                None => continue,
+            };
+
+            let pair = (a.id.min(b.id), a.id.max(b.id));
+            if !connected.contains(&pair) {
+                connected.push(pair);
+                tree.connections.push(Connection {
+                    a: a.clone(),
+                    b,
+                    path,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn add_cluster(
+    tree: &mut Tree,
+    data: &data::Tree,
+    socket: u16,
+    jewel: &ClusterJewel,
+) -> Result<()> {
+    let layout = jewel.size.layout();
+
+    let mut socket_id = socket;
+    let mut expansion = data
+        .expansion_jewel(socket)
+        .ok_or_else(|| invalid("socket does not accept cluster jewels"))?;
+
+    // Smaller jewels in larger sockets take the place of a nested socket.
+    while expansion.size > jewel.size.index() {
+        (socket_id, expansion) = data
+            .nested_sockets(socket_id)
+            .filter(|(_, nested)| nested.size == expansion.size - 1)
+            .min_by_key(|(_, nested)| (nested.index != 1, nested.index))
+            .ok_or_else(|| invalid("socket has no nested socket for a smaller jewel"))?;
+    }
+    if expansion.size < jewel.size.index() {
+        return Err(invalid(format!(
+            "{:?} cluster jewel does not fit into the socket",
+            jewel.size
+        )));
+    }
+
+    let base_id = cluster_hash(data, expansion) + (u32::from(jewel.size.index()) << 4);
+
+    let proxy_id: u16 = expansion
+        .proxy
+        .parse()
+        .map_err(|_| invalid(format!("invalid proxy node id {}", expansion.proxy)))?;
+    let proxy = data
+        .node(proxy_id)
+        .ok_or_else(|| invalid(format!("proxy node {proxy_id} does not exist")))?;
+    // Makes sure the proxy node is placed on a valid orbit.
+    proxy.position()?;
+    let orbit = proxy
+        .orbit
+        .ok_or_else(|| invalid(format!("proxy node {proxy_id} has no orbit")))?
+        as usize;
+    let proxy_orbit_index = proxy.orbit_index.unwrap_or(0) as usize;
+
+    let indices = assign_indices(jewel);
+
+    let (Some(skills_on_orbit), Some(radius)) =
+        (data.skills_per_orbit(orbit), data.orbit_radius(orbit))
+    else {
+        return Err(invalid(format!(
+            "orbit {orbit} of proxy node {proxy_id} does not exist"
+        )));
+    };
+    // Cluster indices are relative to the proxy, in the index space of the cluster.
+    let proxy_index =
+        translate_orbit_index(proxy_orbit_index, skills_on_orbit, layout.total_indices);
+    let mut cluster_nodes = Vec::with_capacity(indices.len());
+    for (&index, slot) in &indices {
+        let orbit_index = translate_orbit_index(
+            (proxy_index + index) % layout.total_indices,
+            layout.total_indices,
+            skills_on_orbit,
+        );
+        let (angle, x, y) = data.orbit_position(proxy.group()?, orbit, orbit_index);
+
+        let (id, kind, meta) = match slot {
+            Slot::Socket(socket_index) => {
+                let (id, _) = data
+                    .nested_sockets(socket_id)
+                    .find(|(_, nested)| usize::from(nested.index) == *socket_index)
+                    .ok_or_else(|| {
+                        invalid(format!("nested socket {socket_index} does not exist"))
+                    })?;
+                let node = data
+                    .node(id)
+                    .ok_or_else(|| invalid(format!("nested socket {id} does not exist")))?;
+                let meta = NodeMeta {
+                    name: node.name.clone(),
+                    stats: node.stats.clone(),
+                };
+                (u32::from(id), NodeKind::Jewel, meta)
+            }
+            Slot::Notable(name) => {
+                let meta = NodeMeta {
+                    name: name.clone(),
+                    stats: data
+                        .cluster_notable(name)
+                        .map(|notable| notable.stats.clone())
+                        .unwrap_or_default(),
+                };
+                (base_id + index as u32, NodeKind::Notable, meta)
+            }
+            Slot::Small => {
+                let meta = NodeMeta {
+                    name: "Small Passive Skill".to_owned(),
+                    stats: jewel.small_stats.clone(),
+                };
+                (base_id + index as u32, NodeKind::Normal, meta)
+            }
+        };
+
+        let position = Coord { x, y };
+        tree.view_box.extend(position);
+        cluster_nodes.push((
+            index,
+            angle,
+            NodeRef {
+                id,
+                position,
+                kind: kind.clone(),
+            },
+        ));
+        tree.nodes.push(Node {
+            id,
+            position,
+            kind,
+            meta,
+        });
+    }
+
+    for pair in cluster_nodes.windows(2) {
+        let [(_, angle, a), (_, out_angle, b)] = pair else {
+            unreachable!()
+        };
+        tree.connections.push(Connection {
+            a: a.clone(),
+            b: b.clone(),
+            path: Path::arc(radius, *angle, *out_angle),
+        });
+    }
+
+    // Full large clusters form a ring.
+    if let (Some(first), Some(last)) = (cluster_nodes.first(), cluster_nodes.last()) {
+        if jewel.size == ClusterSize::Large && first.0 == 0 && last.0 == layout.total_indices - 1 {
+            tree.connections.push(Connection {
+                a: last.2.clone(),
+                b: first.2.clone(),
+                path: Path::arc(radius, last.1, first.1),
+            });
+        }
+    }
+
+    // The entrance of the cluster connects to the socket the jewel is placed in.
+    let entrance = cluster_nodes
+        .iter()
+        .find(|(index, ..)| *index == 0)
+        .map(|(.., node)| node.clone());
+    let parent = tree
+        .nodes
+        .iter()
+        .find(|node| node.id == u32::from(socket))
+        .map(NodeRef::from);
+    if let (Some(parent), Some(entrance)) = (parent, entrance) {
+        tree.connections.push(Connection {
+            a: parent,
+            b: entrance,
+            path: Path::Line {},
+        });
+    }
+
+    Ok(())
+}
+
+/// Translates an orbit index between orbits with `from` and `to` slots.
+///
+/// Orbits with 16 slots are not evenly spaced, they map onto the 12 evenly spaced
+/// cluster indices like in Path of Building.
+fn translate_orbit_index(index: usize, from: usize, to: usize) -> usize {
+    const INDEX_12_TO_16: [usize; 12] = [0, 1, 3, 4, 5, 7, 8, 9, 11, 12, 13, 15];
+    const INDEX_16_TO_12: [usize; 16] = [0, 1, 1, 2, 3, 4, 4, 5, 6, 7, 7, 8, 9, 10, 10, 11];
+
+    match (from, to) {
+        _ if from == to => index,
+        (12, 16) => INDEX_12_TO_16[index],
+        (16, 12) => INDEX_16_TO_12[index],
+        _ => index * to / from,
+    }
+}
+
+enum Slot {
+    /// Nested jewel socket with the index of the socket in the cluster.
+    Socket(usize),
+    Notable(String),
+    Small,
+}
+
+/// Assigns every node of the cluster its index on the cluster orbit.
+///
+/// Follows the placement rules of Path of Building, including the special
+/// cases for medium clusters.
+fn assign_indices(jewel: &ClusterJewel) -> BTreeMap<usize, Slot> {
+    let layout = jewel.size.layout();
+    let mut indices = BTreeMap::new();
+
+    let socket_count = jewel.socket_count.min(layout.socket_indices.len());
+    if jewel.size == ClusterSize::Large && socket_count == 1 {
+        indices.insert(6, Slot::Socket(1));
+    } else {
+        for (i, &index) in layout.socket_indices.iter().take(socket_count).enumerate() {
+            indices.insert(index, Slot::Socket(i));
+        }
+    }
+
+    let node_count = jewel.node_count;
+    let notable_count = jewel
+        .notables
+        .len()
+        .min(node_count.saturating_sub(socket_count));
+    let mut notable_indices = Vec::new();
+    for &index in layout.notable_indices {
+        if notable_indices.len() == notable_count {
+            break;
+        }
+
+        let index = match (jewel.size, socket_count, notable_count, node_count, index) {
+            (ClusterSize::Medium, 0, 2, _, 6) => 4,
+            (ClusterSize::Medium, 0, 2, _, 10) => 8,
+            (ClusterSize::Medium, _, _, 4, 10) => 9,
+            (ClusterSize::Medium, _, _, 4, 2) => 3,
+            _ => index,
+        };
+
+        if !indices.contains_key(&index) {
+            notable_indices.push(index);
+        }
+    }
+    notable_indices.sort();
+    for (index, name) in notable_indices.into_iter().zip(&jewel.notables) {
+        indices.insert(index, Slot::Notable(name.clone()));
+    }
+
+    let small_count = node_count.saturating_sub(socket_count + notable_count);
+    let mut added = 0;
+    for &index in layout.small_indices {
+        if added == small_count {
+            break;
+        }
+
+        let index = match (jewel.size, node_count, index) {
+            (ClusterSize::Medium, 5, 4) => 3,
+            (ClusterSize::Medium, 4, 8) => 9,
+            (ClusterSize::Medium, 4, 4) => 3,
+            _ => index,
+        };
+
+        if let Entry::Vacant(entry) = indices.entry(index) {
+            entry.insert(Slot::Small);
+            added += 1;
+        }
+    }
+
+    indices
+}
+
+/// Base hash of the nodes of a cluster in the socket described by `expansion`.
+///
+/// The index of every large and medium socket on the way to the outer tree
+/// is encoded into the hash.
+fn cluster_hash(data: &data::Tree, expansion: &data::ExpansionJewel) -> u32 {
+    let mut hash = CLUSTER_NODE_OFFSET;
+
+    let mut current = Some(expansion);
+    while let Some(expansion) = current {
+        match expansion.size {
+            2 => hash += u32::from(expansion.index) << 6,
+            1 => hash += u32::from(expansion.index) << 9,
+            _ => {}
+        }
+
+        current = expansion
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.parse().ok())
+            .and_then(|parent| data.expansion_jewel(parent));
+    }
+
+    hash
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::Cluster(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::Passives;
+    use crate::tree;
+
+    #[test]
+    fn translate_orbit_indices() {
+        for index in 0..12 {
+            let translated = translate_orbit_index(index, 12, 16);
+            assert_eq!(translate_orbit_index(translated, 16, 12), index);
+        }
+        assert_eq!(translate_orbit_index(3, 12, 16), 4);
+        assert_eq!(translate_orbit_index(2, 16, 12), 1);
+        assert_eq!(translate_orbit_index(5, 6, 6), 5);
+    }
+
+    /// Generated clusters match the clusters exported by the character window.
+    #[test]
+    fn generated_matches_exported() {
+        let data = data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap();
+        let passives =
+            Passives::new(include_str!("../tests/fixtures/passives-cluster.json")).unwrap();
+        let allocation = passives.allocation(&data).unwrap();
+
+        let mut exported = tree::build(&data, &Default::default()).unwrap();
+        expand(&mut exported, &data, &allocation.jewels).unwrap();
+
+        let mut jewels = allocation.jewels.clone();
+        for jewel in jewels.values_mut() {
+            jewel.nodes.clear();
+        }
+        let mut generated = tree::build(&data, &Default::default()).unwrap();
+        expand(&mut generated, &data, &jewels).unwrap();
+
+        let cluster_nodes = |tree: &Tree| -> Vec<_> {
+            tree.nodes
+                .iter()
+                .filter(|node| node.id >= CLUSTER_NODE_OFFSET)
+                .map(|node| {
+                    (
+                        node.id,
+                        node.position,
+                        node.kind.clone(),
+                        node.meta.name.clone(),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(cluster_nodes(&generated).len(), 8);
+        assert_eq!(cluster_nodes(&generated), cluster_nodes(&exported));
+
+        let notable = generated
+            .nodes
+            .iter()
+            .find(|node| node.id == 65572)
+            .unwrap();
+        assert_eq!(notable.meta.name, "Burning Bright");
+        assert_eq!(
+            notable.meta.stats,
+            [
+                "Damage Penetrates 6% Fire Resistance",
+                "20% increased Fire Damage"
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_all_builds() {
+        let data = data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap();
+        let passives =
+            Passives::new(include_str!("../tests/fixtures/passives-cluster.json")).unwrap();
+        let allocation = passives.allocation(&data).unwrap();
+
+        let mut expected = tree::build(&data, &Default::default()).unwrap();
+        expand(&mut expected, &data, &allocation.jewels).unwrap();
+
+        // The same jewels in both builds are only added once.
+        let mut tree = tree::build(&data, &Default::default()).unwrap();
+        expand_all(&mut tree, &data, &[&allocation.jewels, &allocation.jewels]).unwrap();
+        assert_eq!(tree.nodes, expected.nodes);
+        assert_eq!(tree.connections, expected.connections);
+
+        // Builds without jewels add nothing.
+        let mut tree = tree::build(&data, &Default::default()).unwrap();
+        expand_all(&mut tree, &data, &[&BTreeMap::new(), &allocation.jewels]).unwrap();
+        assert_eq!(tree.nodes, expected.nodes);
+        assert_eq!(tree.connections, expected.connections);
+    }
+}
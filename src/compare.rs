@@ -0,0 +1,248 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::allocation::Allocation;
+use crate::tree::{AscendancyNodeKind, NodeKind, Tree};
+
+/// Comparison of two builds allocated on the same tree.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Comparison {
+    pub a: Summary,
+    pub b: Summary,
+    /// Number of passive points spent on the same nodes in both builds.
+    pub shared: usize,
+}
+
+/// Allocations of one build compared to the other build.
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct Summary {
+    /// Passive points spent outside of the ascendancy.
+    pub points: usize,
+    /// Passive points spent in the ascendancy, the free start node is not included.
+    pub ascendancy_points: usize,
+    /// Passive points spent in the bloodline, they do not count towards the ascendancy points.
+    pub bloodline_points: usize,
+    /// Names of notables, including ascendancy notables, the other build does not allocate.
+    pub notables: Vec<String>,
+    /// Names of keystones the other build does not allocate.
+    pub keystones: Vec<String>,
+    /// Ids and names of all nodes the other build does not allocate, ordered by id.
+    pub nodes: Vec<(u32, String)>,
+}
+
+/// Compares the allocated nodes of `a` and `b` on `tree`.
+///
+/// Nodes which do not exist in the tree are ignored, cluster jewel nodes are only
+/// compared if they were added to `tree` with [`crate::cluster::expand`].
+pub fn compare(tree: &Tree, a: &Allocation, b: &Allocation) -> Comparison {
+    let a = allocated(a);
+    let b = allocated(b);
+
+    let mut comparison = Comparison {
+        a: Summary::default(),
+        b: Summary::default(),
+        shared: 0,
+    };
+
+    for node in &tree.nodes {
+        let in_a = a.contains(&node.id);
+        let in_b = b.contains(&node.id);
+
+        let is_point = !matches!(
+            node.kind,
+            NodeKind::Ascendancy {
+                kind: AscendancyNodeKind::Start,
+                ..
+            }
+        );
+        if in_a && in_b && is_point {
+            comparison.shared += 1;
+        }
+
+        for (summary, allocated, other) in [
+            (&mut comparison.a, in_a, in_b),
+            (&mut comparison.b, in_b, in_a),
+        ] {
+            if !allocated {
+                continue;
+            }
+
+            match &node.kind {
+                _ if !is_point => {}
+                NodeKind::Ascendancy { ascendancy, .. } if tree.bloodlines.contains(ascendancy) => {
+                    summary.bloodline_points += 1
+                }
+                NodeKind::Ascendancy { .. } => summary.ascendancy_points += 1,
+                _ => summary.points += 1,
+            }
+
+            if other {
+                continue;
+            }
+            summary.nodes.push((node.id, node.meta.name.clone()));
+            match node.kind {
+                NodeKind::Keystone => summary.keystones.push(node.meta.name.clone()),
+                NodeKind::Notable
+                | NodeKind::Ascendancy {
+                    kind: AscendancyNodeKind::Notable,
+                    ..
+                } => summary.notables.push(node.meta.name.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    comparison.a.notables.sort();
+    comparison.a.keystones.sort();
+    comparison.b.notables.sort();
+    comparison.b.keystones.sort();
+    comparison.a.nodes.sort();
+    comparison.b.nodes.sort();
+
+    comparison
+}
+
+fn allocated(allocation: &Allocation) -> BTreeSet<u32> {
+    allocation
+        .nodes
+        .iter()
+        .map(|&node| u32::from(node))
+        .chain(allocation.cluster_nodes.iter().copied())
+        .collect()
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, summary) in [("A", &self.a), ("B", &self.b)] {
+            write!(
+                f,
+                "{name}: {} points, {} ascendancy points",
+                summary.points, summary.ascendancy_points
+            )?;
+            if summary.bloodline_points > 0 {
+                write!(f, ", {} bloodline points", summary.bloodline_points)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "Shared: {} points", self.shared)?;
+
+        for (name, summary) in [("A", &self.a), ("B", &self.b)] {
+            if summary.keystones.is_empty() && summary.notables.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "Only in {name}:")?;
+            for keystone in &summary.keystones {
+                writeln!(f, "  Keystone {keystone}")?;
+            }
+            for notable in &summary.notables {
+                writeln!(f, "  Notable {notable}")?;
+            }
+        }
+
+        for (id, name) in &self.b.nodes {
+            writeln!(f, "+ {id} {name}")?;
+        }
+        for (id, name) in &self.a.nodes {
+            writeln!(f, "- {id} {name}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    fn tree() -> Tree {
+        let data = data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap();
+        crate::tree::build(&data, &Default::default()).unwrap()
+    }
+
+    /// Berserker with the Aul bloodline.
+    fn berserker() -> Allocation {
+        Allocation {
+            class: 1,
+            ascendancy: 2,
+            alternate_ascendancy: 3,
+            nodes: vec![201, 101, 102, 1200, 1201, 1500, 1501],
+            ..Default::default()
+        }
+    }
+
+    fn ascendant() -> Allocation {
+        Allocation {
+            class: 0,
+            ascendancy: 1,
+            nodes: vec![101, 102, 103, 1000, 1001],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn points() {
+        let comparison = compare(&tree(), &berserker(), &ascendant());
+
+        assert_eq!(comparison.shared, 2);
+        assert_eq!(comparison.a.points, 3);
+        assert_eq!(comparison.a.ascendancy_points, 1);
+        assert_eq!(comparison.a.bloodline_points, 1);
+        assert_eq!(comparison.b.points, 3);
+        assert_eq!(comparison.b.ascendancy_points, 1);
+        assert_eq!(comparison.b.bloodline_points, 0);
+
+        assert_eq!(
+            comparison.to_string(),
+            "A: 3 points, 1 ascendancy points, 1 bloodline points\n\
+             B: 3 points, 1 ascendancy points\n\
+             Shared: 2 points\n\
+             Only in A:\n  \
+             Notable Crystalline Phylactery\n  \
+             Notable Rite of Ruin\n\
+             Only in B:\n  \
+             Notable Path of the Warrior\n\
+             + 103 Large Jewel Socket\n\
+             + 1000 Ascendant\n\
+             + 1001 Path of the Warrior\n\
+             - 201 Life\n\
+             - 1200 Berserker\n\
+             - 1201 Rite of Ruin\n\
+             - 1500 Aul Bloodline\n\
+             - 1501 Crystalline Phylactery\n"
+        );
+    }
+
+    #[test]
+    fn notables_and_keystones() {
+        let mut tree = tree();
+        let node = tree.nodes.iter_mut().find(|node| node.id == 102).unwrap();
+        node.kind = NodeKind::Keystone;
+
+        let mut a = ascendant();
+        a.nodes.retain(|&node| node != 102);
+        let comparison = compare(&tree, &ascendant(), &a);
+
+        assert_eq!(comparison.shared, 3);
+        assert_eq!(comparison.a.keystones, ["Might"]);
+        assert_eq!(comparison.a.notables, Vec::<String>::new());
+        assert_eq!(comparison.b.keystones, Vec::<String>::new());
+        assert_eq!(comparison.b.notables, Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_nodes() {
+        let mut a = ascendant();
+        a.nodes.push(999);
+        a.cluster_nodes.push(65572);
+
+        let comparison = compare(&tree(), &a, &ascendant());
+        assert_eq!(comparison.shared, 4);
+        assert_eq!(comparison.a.points, 3);
+        assert_eq!(comparison.a.notables, Vec::<String>::new());
+        assert_eq!(comparison.a.nodes, Vec::new());
+    }
+}
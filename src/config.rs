@@ -1,31 +1,97 @@
+use anyhow::Context;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+use tmm::tree;
+
+use crate::cache::{self, Cache};
+use crate::source;
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub out: std::path::PathBuf,
+    /// Directory to cache downloaded tree data in.
+    pub cache: Option<std::path::PathBuf>,
     pub tree: Vec<Tree>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Config {
+    /// Looks up a configured tree by its name.
+    pub fn tree(&self, name: &str) -> Option<&Tree> {
+        self.tree.iter().find(|tree| tree.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Tree {
     pub name: String,
+    /// Expected sha256 checksum of the tree data.
+    pub sha256: Option<String>,
+    #[serde(flatten)]
+    pub options: tree::Options,
     #[serde(flatten)]
     pub location: Location,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub enum Location {
     #[serde(rename = "url")]
     Url(String),
+    /// Local file, optionally gzip or zstd compressed.
     #[serde(rename = "path")]
     Path(std::path::PathBuf),
+    /// File in a local zip or tar archive.
+    #[serde(rename = "archive")]
+    Archive(Archive),
+    /// File in a local git repository.
+    #[serde(rename = "git")]
+    Git(Git),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Archive {
+    pub path: std::path::PathBuf,
+    #[serde(default = "default_file")]
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Git {
+    pub repo: std::path::PathBuf,
+    /// Tag, branch or commit to read the file from.
+    pub rev: String,
+    #[serde(default = "default_file")]
+    pub file: String,
+}
+
+fn default_file() -> String {
+    "data.json".to_owned()
+}
+
+impl Tree {
+    /// Reads the tree data and verifies its checksum, downloads go through the `cache`.
+    pub fn read(&self, cache: &Cache) -> anyhow::Result<String> {
+        let sha256 = self.sha256.as_deref();
+
+        let data = match &self.location {
+            // Downloads are verified by the cache before they are cached.
+            Location::Url(url) => cache.fetch(url, sha256),
+            Location::Path(path) => verified(source::read_path(path), sha256),
+            Location::Archive(archive) => {
+                verified(source::read_archive(&archive.path, &archive.file), sha256)
+            }
+            Location::Git(git) => {
+                verified(source::read_git(&git.repo, &git.rev, &git.file), sha256)
+            }
+        };
+
+        data.with_context(|| format!("failed to read tree {}", self.name))
+    }
 }
 
-impl Location {
-    pub fn read(&self) -> anyhow::Result<String> {
-        match self {
-            Location::Url(url) => Ok(ureq::get(url).call()?.into_string()?),
-            Location::Path(path) => Ok(std::fs::read_to_string(path)?),
-        }
+fn verified(data: anyhow::Result<String>, sha256: Option<&str>) -> anyhow::Result<String> {
+    let data = data?;
+    if let Some(sha256) = sha256 {
+        cache::verify(&data, sha256)?;
     }
+    Ok(data)
 }
@@ -1,8 +1,11 @@
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::fmt;
 use std::ops::Deref;
 
 use poe_api::api::*;
+use serde::Deserialize;
 
 const TWO_PI: f32 = 2.0 * PI;
 
@@ -14,67 +17,476 @@ const ANGLES_40: [u32; 40] = [
     190, 200, 210, 220, 225, 230, 240, 250, 260, 270, 280, 290, 300, 310, 315, 320, 330, 340, 350,
 ];
 
+/// The tree data of a tree version, as exported by GGG.
 pub struct Tree {
+    /// Name of the tree version, e.g. `3.25`.
+    pub version: String,
     pub data: SkillTreeData,
+    extra: Extra,
+    /// Angle of every orbit index in radians, by orbit.
+    orbit_angles: Vec<Vec<f32>>,
 }
 
+/// Parts of the tree export which are not part of [`SkillTreeData`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Extra {
+    #[serde(default)]
+    jewel_slots: Vec<u16>,
+    #[serde(default)]
+    nodes: HashMap<String, ExtraNode>,
+    #[serde(default, alias = "alternate_ascendancies")]
+    alternate_ascendancies: Vec<ExtraAscendancy>,
+    #[serde(default)]
+    constants: ExtraConstants,
+    #[serde(default)]
+    points: Option<Points>,
+}
+
+/// Passive point limits of a tree version.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Points {
+    /// Passive points, not including ascendancy points.
+    pub total_points: u32,
+    pub ascendancy_points: u32,
+    /// Points of bloodline ascendancies, which do not use ascendancy points.
+    ///
+    /// `None` if the export does not limit them.
+    #[serde(default)]
+    pub bloodline_points: Option<u32>,
+}
+
+impl Default for Points {
+    /// Limits of recent tree versions, for exports without `points`.
+    fn default() -> Self {
+        Self {
+            total_points: 123,
+            ascendancy_points: 8,
+            bloodline_points: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtraConstants {
+    /// Angle of every orbit index in degrees, by orbit.
+    ///
+    /// Not part of older exports, which use hardcoded angles instead.
+    #[serde(default)]
+    orbit_angles_by_orbit: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtraNode {
+    expansion_jewel: Option<ExpansionJewel>,
+    ascendancy_name: Option<String>,
+    #[serde(default)]
+    is_bloodline: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtraAscendancy {
+    id: String,
+    #[serde(default)]
+    is_bloodline: bool,
+}
+
+/// Cluster jewel socket metadata of a jewel socket node.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExpansionJewel {
+    /// Size of the socket, `0` for small, `1` for medium and `2` for large sockets.
+    pub size: u8,
+    /// Index of the socket within the parent cluster.
+    pub index: u8,
+    /// Node id of the proxy node, its group is used to place the cluster nodes.
+    pub proxy: String,
+    /// Node id of the socket this socket is nested in.
+    pub parent: Option<String>,
+}
+
+/// A problem with the tree data of a tree version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataError {
+    /// Name of the tree version.
+    pub version: String,
+    /// Where in the tree data the problem is.
+    pub location: Location,
+    /// Name of the field in the tree data.
+    pub field: &'static str,
+    pub kind: DataErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Node(String),
+    Group(String),
+    Constants,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataErrorKind {
+    /// The field is required but missing.
+    Missing,
+    /// Node id is not a valid node id.
+    InvalidId,
+    /// References a node which does not exist.
+    UnknownNode(String),
+    /// References a group which does not exist.
+    UnknownGroup(String),
+    /// References an ascendancy which is neither a class nor an alternate ascendancy.
+    UnknownAscendancy(String),
+    /// Index is out of range.
+    OutOfRange { index: usize, len: usize },
+    /// Amount of orbit angles does not match the skills on the orbit.
+    AngleCount { angles: usize, skills: usize },
+    /// There are fewer orbit radii than orbits.
+    RadiusCount { radii: usize, orbits: usize },
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tree {}: ", self.version)?;
+        match &self.location {
+            Location::Node(id) => write!(f, "node {id}")?,
+            Location::Group(id) => write!(f, "group {id}")?,
+            Location::Constants => write!(f, "constants")?,
+        }
+        write!(f, ", field `{}`: ", self.field)?;
+
+        match &self.kind {
+            DataErrorKind::Missing => write!(f, "missing"),
+            DataErrorKind::InvalidId => write!(f, "invalid node id"),
+            DataErrorKind::UnknownNode(id) => write!(f, "unknown node {id}"),
+            DataErrorKind::UnknownGroup(id) => write!(f, "unknown group {id}"),
+            DataErrorKind::UnknownAscendancy(name) => write!(f, "unknown ascendancy {name}"),
+            DataErrorKind::OutOfRange { index, len } => {
+                write!(f, "{index} is out of range, there are only {len}")
+            }
+            DataErrorKind::AngleCount { angles, skills } => {
+                write!(f, "{angles} angles for an orbit with {skills} skills")
+            }
+            DataErrorKind::RadiusCount { radii, orbits } => {
+                write!(f, "{radii} radii for {orbits} orbits")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
 impl Tree {
-    pub fn new(s: &str) -> anyhow::Result<Self> {
-        let data: poe_api::api::SkillTreeData = serde_json::from_str(s)?;
-        Ok(Self { data })
+    /// Parses the tree export `s` of the tree version named `version`.
+    ///
+    /// Problems with the contents are not reported here, see [`Tree::check`].
+    pub fn new(version: &str, s: &str) -> crate::Result<Self> {
+        // Both are read from the same export, only parse the document once.
+        let value: serde_json::Value = serde_json::from_str(s)?;
+        let extra = Extra::deserialize(&value)?;
+        let data = SkillTreeData::deserialize(value)?;
+
+        // Mismatched angles are reported by `check`, fall back to the defaults instead.
+        let orbit_angles = data
+            .constants
+            .skills_per_orbit
+            .iter()
+            .enumerate()
+            .map(|(orbit, &skills)| {
+                let skills = skills as usize;
+                match extra.constants.orbit_angles_by_orbit.get(orbit) {
+                    Some(angles) if angles.len() == skills => {
+                        angles.iter().map(|angle| angle.to_radians()).collect()
+                    }
+                    _ => default_orbit_angles(skills),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            version: version.to_owned(),
+            data,
+            extra,
+            orbit_angles,
+        })
+    }
+
+    /// Collects every problem in the tree data.
+    ///
+    /// Building a tree stops at the first problem, this reports all of them.
+    pub fn check(&self) -> Vec<DataError> {
+        let mut problems = Vec::new();
+
+        let skills_per_orbit = &self.data.constants.skills_per_orbit;
+        for (orbit, angles) in self
+            .extra
+            .constants
+            .orbit_angles_by_orbit
+            .iter()
+            .enumerate()
+        {
+            let skills = skills_per_orbit
+                .get(orbit)
+                .map_or(0, |&skills| skills as usize);
+            if angles.len() != skills {
+                problems.push(self.error(
+                    Location::Constants,
+                    "orbitAnglesByOrbit",
+                    DataErrorKind::AngleCount {
+                        angles: angles.len(),
+                        skills,
+                    },
+                ));
+            }
+        }
+
+        let radii = self.data.constants.orbit_radii.len();
+        if radii < skills_per_orbit.len() {
+            problems.push(self.error(
+                Location::Constants,
+                "orbitRadii",
+                DataErrorKind::RadiusCount {
+                    radii,
+                    orbits: skills_per_orbit.len(),
+                },
+            ));
+        }
+
+        let mut grouped = HashMap::new();
+        for group in self.groups() {
+            for (id, node) in group.inner.nodes.iter().zip(group.nodes()) {
+                match node {
+                    Ok(_) => {
+                        grouped.insert(id.as_str(), group.inner);
+                    }
+                    Err(err) => problems.push(err),
+                }
+            }
+        }
+
+        // Also visit nodes which are not part of any group, groups do not reference them.
+        for (id, node) in &self.data.nodes {
+            // The root connects the class starts, it is not a real node.
+            if id == "root" {
+                continue;
+            }
+
+            let group = grouped.get(id.as_str()).copied();
+            let node = match parse_id(self, id) {
+                Ok(id) => Node {
+                    id,
+                    inner: node,
+                    groupx: Cell::new(group),
+                    parent: self,
+                },
+                Err(err) => {
+                    problems.push(err);
+                    continue;
+                }
+            };
+
+            // Cluster notables have no group and no position, only their references matter.
+            if group.is_some() || node.inner.group.is_some() {
+                if let Err(err) = node.position() {
+                    problems.push(err);
+                }
+            }
+            problems.extend(node.out().filter_map(Result::err));
+
+            if let Some(name) = &node.ascendancy_name {
+                if !self.is_ascendancy(name) {
+                    problems.push(node.error(
+                        "ascendancyName",
+                        DataErrorKind::UnknownAscendancy(name.clone()),
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    fn is_ascendancy(&self, name: &str) -> bool {
+        let class = self
+            .data
+            .classes
+            .iter()
+            .flat_map(|class| &class.ascendancies)
+            .any(|asc| asc.name == name);
+        class
+            || self
+                .data
+                .alternate_ascendancies
+                .iter()
+                .any(|asc| asc.id == name)
+    }
+
+    fn error(&self, location: Location, field: &'static str, kind: DataErrorKind) -> DataError {
+        DataError {
+            version: self.version.clone(),
+            location,
+            field,
+            kind,
+        }
+    }
+
+    /// Whether the alternate ascendancy `id` is a bloodline, which is allocated in
+    /// addition to the class ascendancy instead of replacing it.
+    ///
+    /// Bloodlines are flagged with `isBloodline`, either on the ascendancy or on its nodes.
+    pub fn is_bloodline(&self, id: &str) -> bool {
+        let ascendancy = self
+            .extra
+            .alternate_ascendancies
+            .iter()
+            .any(|asc| asc.id == id && asc.is_bloodline);
+        ascendancy
+            || self
+                .extra
+                .nodes
+                .values()
+                .any(|node| node.is_bloodline && node.ascendancy_name.as_deref() == Some(id))
+    }
+
+    /// Passive point limits of the tree version, `None` if the export does not contain them.
+    pub fn points(&self) -> Option<Points> {
+        self.extra.points
+    }
+
+    /// Node ids of all jewel sockets, in the order referenced by socket indices.
+    pub fn jewel_slots(&self) -> &[u16] {
+        &self.extra.jewel_slots
+    }
+
+    pub fn node(&self, id: u16) -> Option<Node<'_>> {
+        self.data.nodes.get(&id.to_string()).map(|node| Node {
+            id,
+            inner: node,
+            groupx: Cell::new(None),
+            parent: self,
+        })
+    }
+
+    /// Notable added by cluster jewels, looked up by name.
+    ///
+    /// Cluster notables are part of the tree data, but not placed in any group.
+    pub fn cluster_notable(&self, name: &str) -> Option<&SkillTreeNode> {
+        self.data
+            .nodes
+            .values()
+            .find(|node| node.group.is_none() && node.is_notable && node.name == name)
+    }
+
+    /// Cluster jewel socket metadata of the node, only present on jewel sockets
+    /// which accept cluster jewels.
+    pub fn expansion_jewel(&self, id: u16) -> Option<&ExpansionJewel> {
+        self.extra
+            .nodes
+            .get(&id.to_string())?
+            .expansion_jewel
+            .as_ref()
+    }
+
+    /// All cluster jewel sockets nested in the socket `parent`.
+    pub fn nested_sockets(&self, parent: u16) -> impl Iterator<Item = (u16, &ExpansionJewel)> {
+        let parent = parent.to_string();
+        self.extra.nodes.iter().filter_map(move |(id, node)| {
+            let jewel = node.expansion_jewel.as_ref()?;
+            (jewel.parent.as_ref() == Some(&parent)).then(|| Some((id.parse().ok()?, jewel)))?
+        })
+    }
+
+    /// Amount of skill slots on `orbit`, `None` if the orbit does not exist.
+    pub fn skills_per_orbit(&self, orbit: usize) -> Option<usize> {
+        self.data
+            .constants
+            .skills_per_orbit
+            .get(orbit)
+            .map(|&skills| skills as usize)
+    }
+
+    /// Radius of `orbit`, `None` if the orbit does not exist.
+    ///
+    /// The orbit of a node with a valid [`Node::position`] always has a radius.
+    pub fn orbit_radius(&self, orbit: usize) -> Option<u32> {
+        self.data.constants.orbit_radii.get(orbit).copied()
     }
 
     pub fn groups(&self) -> impl Iterator<Item = Group<'_>> {
-        self.data.groups.values().map(|group| Group {
+        self.data.groups.iter().map(|(id, group)| Group {
+            id,
             inner: group,
             parent: self,
         })
     }
 
-    fn get_position(&self, node: &SkillTreeNode, group: &SkillTreeGroup) -> (f32, i32, i32) {
-        let radius = self.data.constants.orbit_radii[node.orbit.unwrap() as usize] as f32;
-        let skills_on_orbit = self.data.constants.skills_per_orbit[node.orbit.unwrap() as usize];
-        let orbit_index = node.orbit_index.unwrap_or(0);
+    /// Position of slot `orbit_index` on `orbit` around `group` as `(angle, x, y)`.
+    pub fn orbit_position(
+        &self,
+        group: &SkillTreeGroup,
+        orbit: usize,
+        orbit_index: usize,
+    ) -> (f32, i32, i32) {
+        self.orbit_position_at(group.x, group.y, orbit, orbit_index)
+            .expect("orbit slot should exist")
+    }
 
-        let angle = match skills_on_orbit {
-            16 => (ANGLES_16[orbit_index as usize] as f32).to_radians(),
-            40 => (ANGLES_40[orbit_index as usize] as f32).to_radians(),
-            soo => TWO_PI / soo as f32 * orbit_index as f32,
-        };
+    /// Position of slot `orbit_index` on `orbit` around `(x, y)` as `(angle, x, y)`,
+    /// `None` if there is no such slot.
+    pub fn orbit_position_at(
+        &self,
+        x: f32,
+        y: f32,
+        orbit: usize,
+        orbit_index: usize,
+    ) -> Option<(f32, i32, i32)> {
+        let radius = *self.data.constants.orbit_radii.get(orbit)? as f32;
+        let angle = *self.orbit_angles.get(orbit)?.get(orbit_index)?;
 
-        let x = group.x + radius * angle.sin();
-        let y = group.y - radius * angle.cos();
+        let x = x + radius * angle.sin();
+        let y = y - radius * angle.cos();
 
-        (angle % TWO_PI, x as i32, y as i32)
+        Some((angle % TWO_PI, x as i32, y as i32))
+    }
+}
+
+/// Angles of an orbit with `skills` slots, for exports without orbit angles.
+fn default_orbit_angles(skills: usize) -> Vec<f32> {
+    match skills {
+        16 => ANGLES_16.iter().map(|&a| (a as f32).to_radians()).collect(),
+        40 => ANGLES_40.iter().map(|&a| (a as f32).to_radians()).collect(),
+        _ => (0..skills)
+            .map(|i| TWO_PI / skills as f32 * i as f32)
+            .collect(),
     }
 }
 
 pub struct Group<'a> {
+    id: &'a str,
     inner: &'a SkillTreeGroup,
     parent: &'a Tree,
 }
 
 impl<'a> Group<'a> {
-    pub fn nodes(&self) -> impl Iterator<Item = Node<'_>> {
-        self.inner
-            .nodes
-            .iter()
-            .map(|node_id| {
-                (
-                    node_id,
-                    self.parent
-                        .data
-                        .nodes
-                        .get(node_id)
-                        .expect("group contains unknown node"),
+    pub fn nodes(&self) -> impl Iterator<Item = Result<Node<'_>, DataError>> {
+        self.inner.nodes.iter().map(|id| {
+            let node = self.parent.data.nodes.get(id).ok_or_else(|| {
+                self.parent.error(
+                    Location::Group(self.id.to_owned()),
+                    "nodes",
+                    DataErrorKind::UnknownNode(id.clone()),
                 )
-            })
-            .map(|(id, node)| Node {
-                id: id.parse().unwrap(),
+            })?;
+
+            Ok(Node {
+                id: parse_id(self.parent, id)?,
                 inner: node,
                 groupx: Cell::new(Some(self.inner)),
                 parent: self.parent,
             })
+        })
     }
 }
 
@@ -98,33 +510,99 @@ impl<'a> Node<'a> {
         self.id
     }
 
-    pub fn position(&self) -> (f32, i32, i32) {
-        self.parent.get_position(self.inner, self.group())
+    /// Position of the node as `(angle, x, y)`.
+    pub fn position(&self) -> Result<(f32, i32, i32), DataError> {
+        let group = self.group()?;
+
+        let orbit =
+            self.inner
+                .orbit
+                .ok_or_else(|| self.error("orbit", DataErrorKind::Missing))? as usize;
+        let Some(angles) = self.parent.orbit_angles.get(orbit) else {
+            return Err(self.error(
+                "orbit",
+                DataErrorKind::OutOfRange {
+                    index: orbit,
+                    len: self.parent.orbit_angles.len(),
+                },
+            ));
+        };
+
+        let radii = self.parent.data.constants.orbit_radii.len();
+        if orbit >= radii {
+            return Err(self.error(
+                "orbit",
+                DataErrorKind::OutOfRange {
+                    index: orbit,
+                    len: radii,
+                },
+            ));
+        }
+
+        let orbit_index = self.inner.orbit_index.unwrap_or(0) as usize;
+        if orbit_index >= angles.len() {
+            return Err(self.error(
+                "orbitIndex",
+                DataErrorKind::OutOfRange {
+                    index: orbit_index,
+                    len: angles.len(),
+                },
+            ));
+        }
+
+        Ok(self.parent.orbit_position(group, orbit, orbit_index))
     }
 
-    pub fn out(&self) -> impl Iterator<Item = Node<'_>> {
-        self.inner
-            .out
-            .iter()
-            .map(|id| (id, self.parent.data.nodes.get(id).unwrap()))
-            .map(|(id, node)| Node {
-                id: id.parse().unwrap(),
+    pub fn out(&self) -> impl Iterator<Item = Result<Node<'a>, DataError>> + '_ {
+        self.inner.out.iter().map(|id| {
+            let node = self
+                .parent
+                .data
+                .nodes
+                .get(id)
+                .ok_or_else(|| self.error("out", DataErrorKind::UnknownNode(id.clone())))?;
+
+            Ok(Node {
+                id: parse_id(self.parent, id)?,
                 inner: node,
                 parent: self.parent,
                 groupx: Cell::new(None),
             })
+        })
     }
 
-    fn group(&self) -> &SkillTreeGroup {
+    pub fn group(&self) -> Result<&SkillTreeGroup, DataError> {
         if let Some(group) = self.groupx.get() {
-            return group;
+            return Ok(group);
         }
 
-        let group_id = self.inner.group.unwrap().to_string();
-        let group = self.parent.data.groups.get(&group_id).unwrap();
+        let group_id = self
+            .inner
+            .group
+            .ok_or_else(|| self.error("group", DataErrorKind::Missing))?
+            .to_string();
+        let group =
+            self.parent.data.groups.get(&group_id).ok_or_else(|| {
+                self.error("group", DataErrorKind::UnknownGroup(group_id.clone()))
+            })?;
         self.groupx.set(Some(group));
-        group
+        Ok(group)
     }
+
+    fn error(&self, field: &'static str, kind: DataErrorKind) -> DataError {
+        self.parent
+            .error(Location::Node(self.id.to_string()), field, kind)
+    }
+}
+
+fn parse_id(tree: &Tree, id: &str) -> Result<u16, DataError> {
+    id.parse().map_err(|_| {
+        tree.error(
+            Location::Node(id.to_owned()),
+            "id",
+            DataErrorKind::InvalidId,
+        )
+    })
 }
 
 impl<'a> Deref for Node<'a> {
@@ -134,3 +612,63 @@ impl<'a> Deref for Node<'a> {
         self.inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(f: impl FnOnce(&mut serde_json::Value)) -> Tree {
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+        f(&mut value);
+        Tree::new("test", &value.to_string()).unwrap()
+    }
+
+    #[test]
+    fn check_fixture() {
+        assert_eq!(tree(|_| {}).check(), Vec::new());
+    }
+
+    #[test]
+    fn check_ungrouped_nodes() {
+        let tree = tree(|value| {
+            value["nodes"]["30000"]["out"] = serde_json::json!(["99999"]);
+            value["nodes"]["30002"] = serde_json::json!({
+                "skill": 30002, "name": "Orphan", "group": 77, "orbit": 0, "out": [], "in": []
+            });
+        });
+
+        let kinds: Vec<_> = tree.check().into_iter().map(|err| err.kind).collect();
+        assert_eq!(kinds.len(), 2);
+        assert!(kinds.contains(&DataErrorKind::UnknownNode("99999".to_owned())));
+        assert!(kinds.contains(&DataErrorKind::UnknownGroup("77".to_owned())));
+    }
+
+    #[test]
+    fn check_orbit_radii() {
+        let tree = tree(|value| {
+            value["constants"]["orbitRadii"] = serde_json::json!([0, 82]);
+        });
+
+        let problems = tree.check();
+        assert_eq!(
+            problems[0].kind,
+            DataErrorKind::RadiusCount {
+                radii: 2,
+                orbits: 4
+            }
+        );
+        // Nodes on the missing orbits are reported instead of panicking.
+        assert!(problems.len() > 1);
+        for err in &problems[1..] {
+            assert_eq!(err.field, "orbit");
+            assert!(matches!(err.kind, DataErrorKind::OutOfRange { len: 2, .. }));
+        }
+
+        assert_eq!(tree.orbit_radius(1), Some(82));
+        assert_eq!(tree.orbit_radius(2), None);
+        assert_eq!(tree.skills_per_orbit(3), Some(16));
+        assert_eq!(tree.skills_per_orbit(4), None);
+        assert_eq!(tree.orbit_position_at(0.0, 0.0, 3, 0), None);
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::data;
+use crate::tree::{self, Coord, NodeKind};
+
+/// Changes of the passive nodes between two tree versions.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct Diff {
+    /// Version of the old tree.
+    pub old: String,
+    /// Version of the new tree.
+    pub new: String,
+    /// Changed nodes, unchanged nodes are omitted.
+    pub nodes: Vec<NodeDiff>,
+}
+
+/// A node which changed between two tree versions.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct NodeDiff {
+    pub category: Category,
+    pub changes: Vec<Change>,
+    /// The node in the old tree, `None` if the node was added.
+    pub old: Option<NodeInfo>,
+    /// The node in the new tree, `None` if the node was removed.
+    pub new: Option<NodeInfo>,
+}
+
+/// How a node changed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    /// The node changed its position, e.g. it moved to another group or orbit.
+    Moved,
+    /// The node changed its name.
+    Renamed,
+    /// The node changed its id, but was matched by its name or position.
+    IdChanged,
+    StatsChanged,
+}
+
+impl Change {
+    /// Name of the change as used in the reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Moved => "moved",
+            Self::Renamed => "renamed",
+            Self::IdChanged => "id_changed",
+            Self::StatsChanged => "stats_changed",
+        }
+    }
+}
+
+/// Section of the report a node belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "name")]
+pub enum Category {
+    Keystone,
+    Ascendancy(String),
+    Passive,
+}
+
+/// A node as it appears in one of the tree versions.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct NodeInfo {
+    pub id: u32,
+    pub name: String,
+    pub kind: &'static str,
+    pub x: i32,
+    pub y: i32,
+    pub stats: Vec<String>,
+}
+
+/// How far a node can move and still be matched by its name, roughly the
+/// diameter of the largest orbit.
+///
+/// Many small passives share their name, e.g. `Strength`, they must not be
+/// matched to a node on the other side of the tree.
+pub const MAX_MOVE_DISTANCE: i64 = 1_500;
+
+/// Options to lay out trees for a diff.
+///
+/// Ascendancies keep their positions from the tree data, other layouts move them
+/// depending on the ascendancies of a version or place them on top of each other.
+pub fn options() -> tree::Options {
+    tree::Options::new().ascendancy_layout(tree::AscendancyLayout::Original)
+}
+
+/// Lays out both trees with [`options`] and compares their nodes.
+pub fn diff(old: &data::Tree, new: &data::Tree) -> crate::Result<Diff> {
+    let old_tree = tree::build(old, &options())?;
+    let new_tree = tree::build(new, &options())?;
+
+    Ok(Diff::new(&old.version, &old_tree, &new.version, &new_tree))
+}
+
+impl Diff {
+    /// Compares the nodes of two laid out trees.
+    ///
+    /// Nodes are matched by their id first. Remaining nodes are matched to a node
+    /// with the same name within [`MAX_MOVE_DISTANCE`] to find moved nodes, or to a
+    /// node at the same position to find renamed nodes.
+    pub fn new(old_version: &str, old: &tree::Tree, new_version: &str, new: &tree::Tree) -> Self {
+        let mut old_nodes: BTreeMap<_, _> = old.nodes.iter().map(|node| (node.id, node)).collect();
+        let mut new_nodes: BTreeMap<_, _> = new.nodes.iter().map(|node| (node.id, node)).collect();
+
+        let mut pairs = Vec::new();
+        new_nodes.retain(|id, new| match old_nodes.remove(id) {
+            Some(old) => {
+                pairs.push((old, *new));
+                false
+            }
+            None => true,
+        });
+
+        // Remaining nodes are matched if they kept their name and stayed close, or
+        // kept their position. Every candidate is scored on both, the closest pairs
+        // are matched first and an unchanged name breaks ties.
+        let mut candidates = Vec::new();
+        for new in new_nodes.values() {
+            for old in old_nodes.values() {
+                if !same_kind(&old.kind, &new.kind) {
+                    continue;
+                }
+                let distance = distance(old.position, new.position);
+                let renamed = old.meta.name != new.meta.name;
+                if (renamed && distance == 0) || (!renamed && distance <= MAX_MOVE_DISTANCE.pow(2))
+                {
+                    candidates.push((distance, renamed, old.id, new.id));
+                }
+            }
+        }
+        candidates.sort_unstable();
+
+        for (_, _, old_id, new_id) in candidates {
+            if let (Some(&old), Some(&new)) = (old_nodes.get(&old_id), new_nodes.get(&new_id)) {
+                old_nodes.remove(&old_id);
+                new_nodes.remove(&new_id);
+                pairs.push((old, new));
+            }
+        }
+
+        let mut nodes: Vec<_> = pairs
+            .into_iter()
+            .filter_map(|(old, new)| {
+                let changes: Vec<_> = [
+                    (old.position != new.position, Change::Moved),
+                    (old.meta.name != new.meta.name, Change::Renamed),
+                    (old.id != new.id, Change::IdChanged),
+                    (old.meta.stats != new.meta.stats, Change::StatsChanged),
+                ]
+                .into_iter()
+                .filter_map(|(changed, change)| changed.then_some(change))
+                .collect();
+
+                (!changes.is_empty()).then(|| NodeDiff {
+                    category: Category::new(&new.kind),
+                    changes,
+                    old: Some(NodeInfo::new(old)),
+                    new: Some(NodeInfo::new(new)),
+                })
+            })
+            .chain(old_nodes.into_values().map(|old| NodeDiff {
+                category: Category::new(&old.kind),
+                changes: vec![Change::Removed],
+                old: Some(NodeInfo::new(old)),
+                new: None,
+            }))
+            .chain(new_nodes.into_values().map(|new| NodeDiff {
+                category: Category::new(&new.kind),
+                changes: vec![Change::Added],
+                old: None,
+                new: Some(NodeInfo::new(new)),
+            }))
+            .collect();
+
+        nodes.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+
+        Self {
+            old: old_version.to_owned(),
+            new: new_version.to_owned(),
+            nodes,
+        }
+    }
+
+    /// Number of nodes with the change.
+    pub fn count(&self, change: Change) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.changes.contains(&change))
+            .count()
+    }
+
+    /// Renders the report as JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Renders the report as Markdown, grouped into keystones, ascendancies and other passives.
+    pub fn to_markdown(&self) -> String {
+        let mut output = String::new();
+
+        macro_rules! w {
+            ($($tts:tt)*) => {{
+                let _ = writeln!(output, $($tts)*);
+            }}
+        }
+
+        w!("# Passive tree changes from {} to {}", self.old, self.new);
+        w!();
+
+        if self.nodes.is_empty() {
+            w!("No passive nodes changed.");
+            return output;
+        }
+
+        w!("| Added | Removed | Moved | Renamed | Id changed | Stats changed |");
+        w!("| ---: | ---: | ---: | ---: | ---: | ---: |");
+        w!(
+            "| {} | {} | {} | {} | {} | {} |",
+            self.count(Change::Added),
+            self.count(Change::Removed),
+            self.count(Change::Moved),
+            self.count(Change::Renamed),
+            self.count(Change::IdChanged),
+            self.count(Change::StatsChanged),
+        );
+
+        let mut category = None;
+        for node in &self.nodes {
+            if category != Some(&node.category) {
+                category = Some(&node.category);
+
+                w!();
+                match &node.category {
+                    Category::Keystone => w!("## Keystones"),
+                    Category::Ascendancy(name) => w!("## Ascendancy: {name}"),
+                    Category::Passive => w!("## Passives"),
+                }
+                w!();
+            }
+
+            match (&node.old, &node.new) {
+                (None, Some(new)) => {
+                    w!("- Added **{}** ({})", new.name, new.id);
+                    for stat in &new.stats {
+                        w!("  - {}", stat.replace('\n', " "));
+                    }
+                }
+                (Some(old), None) => {
+                    w!("- Removed **{}** ({})", old.name, old.id);
+                    for stat in &old.stats {
+                        w!("  - ~~{}~~", stat.replace('\n', " "));
+                    }
+                }
+                (Some(old), Some(new)) => {
+                    let changes: Vec<_> = node
+                        .changes
+                        .iter()
+                        .map(|change| match change {
+                            Change::Moved => format!(
+                                "moved from ({}, {}) to ({}, {})",
+                                old.x, old.y, new.x, new.y
+                            ),
+                            Change::Renamed => format!("renamed from **{}**", old.name),
+                            Change::IdChanged => format!("id changed from {}", old.id),
+                            Change::StatsChanged => "stats changed".to_owned(),
+                            Change::Added | Change::Removed => unreachable!(),
+                        })
+                        .collect();
+
+                    w!(
+                        "- Changed **{}** ({}): {}",
+                        new.name,
+                        new.id,
+                        changes.join(", ")
+                    );
+                    if node.changes.contains(&Change::StatsChanged) {
+                        for stat in old.stats.iter().filter(|stat| !new.stats.contains(stat)) {
+                            w!("  - ~~{}~~", stat.replace('\n', " "));
+                        }
+                        for stat in new.stats.iter().filter(|stat| !old.stats.contains(stat)) {
+                            w!("  - {}", stat.replace('\n', " "));
+                        }
+                    }
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        output
+    }
+}
+
+impl NodeDiff {
+    fn sort_key(&self) -> (&Category, Change, &str, u32) {
+        let node = self.new.as_ref().or(self.old.as_ref());
+        (
+            &self.category,
+            self.changes[0],
+            node.map_or("", |node| &node.name),
+            node.map_or(0, |node| node.id),
+        )
+    }
+}
+
+impl Category {
+    fn new(kind: &NodeKind) -> Self {
+        match kind {
+            NodeKind::Keystone => Self::Keystone,
+            NodeKind::Ascendancy { ascendancy, .. } => {
+                Self::Ascendancy(ascendancy.as_ref().to_owned())
+            }
+            _ => Self::Passive,
+        }
+    }
+}
+
+impl NodeInfo {
+    fn new(node: &tree::Node) -> Self {
+        Self {
+            id: node.id,
+            name: node.meta.name.clone(),
+            kind: node.kind.as_str(),
+            x: node.position.x,
+            y: node.position.y,
+            stats: node.meta.stats.clone(),
+        }
+    }
+}
+
+/// Only nodes of the same category can be matched, ascendancy nodes need to be
+/// of the same ascendancy.
+fn same_kind(a: &NodeKind, b: &NodeKind) -> bool {
+    Category::new(a) == Category::new(b)
+}
+
+fn distance(a: Coord, b: Coord) -> i64 {
+    let dx = i64::from(a.x - b.x);
+    let dy = i64::from(a.y - b.y);
+    dx * dx + dy * dy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::{Ascendancy, AscendancyNodeKind, Node, NodeMeta, ViewBox};
+
+    fn node(id: u32, name: &str, kind: NodeKind, (x, y): (i32, i32), stat: &str) -> Node {
+        Node {
+            id,
+            position: Coord { x, y },
+            kind,
+            meta: NodeMeta {
+                name: name.to_owned(),
+                stats: vec![stat.to_owned()],
+            },
+        }
+    }
+
+    fn tree(nodes: Vec<Node>) -> tree::Tree {
+        tree::Tree {
+            view_box: ViewBox {
+                x: 0,
+                y: 0,
+                dx: 0,
+                dy: 0,
+            },
+            nodes,
+            connections: Vec::new(),
+            ascendancies: BTreeMap::new(),
+            alternate_ascendancies: Default::default(),
+            bloodlines: Default::default(),
+            class_starts: Vec::new(),
+            masteries: BTreeMap::new(),
+        }
+    }
+
+    fn berserker() -> NodeKind {
+        NodeKind::Ascendancy {
+            kind: AscendancyNodeKind::Notable,
+            ascendancy: Ascendancy::new("Berserker"),
+        }
+    }
+
+    fn diff(old: Vec<Node>, new: Vec<Node>) -> Diff {
+        Diff::new("3.24", &tree(old), "3.25", &tree(new))
+    }
+
+    /// Changes of the node with the new id, or the old id for removed nodes.
+    fn changes(diff: &Diff, id: u32) -> Vec<Change> {
+        let node = diff
+            .nodes
+            .iter()
+            .find(|node| node.new.as_ref().or(node.old.as_ref()).unwrap().id == id)
+            .unwrap_or_else(|| panic!("node {id} did not change"));
+        node.changes.clone()
+    }
+
+    #[test]
+    fn changes_by_id() {
+        use NodeKind::*;
+
+        let old = vec![
+            node(1, "Strength", Normal, (0, 0), "+10 to Strength"),
+            node(2, "Might", Notable, (100, 0), "10% increased Damage"),
+            node(3, "Cleave", Notable, (200, 0), "Cleave"),
+            node(4, "Brute Force", Notable, (300, 0), "Brute Force"),
+            node(6, "Dexterity", Normal, (0, 500), "+10 to Dexterity"),
+        ];
+        let new = vec![
+            node(1, "Strength", Normal, (0, 0), "+10 to Strength"),
+            node(2, "Might", Notable, (100, 0), "12% increased Damage"),
+            node(3, "Cleave", Notable, (250, 0), "Cleave"),
+            node(4, "Brutal Force", Notable, (300, 0), "Brute Force"),
+            node(7, "Intelligence", Normal, (0, 600), "+10 to Intelligence"),
+        ];
+
+        let diff = diff(old, new);
+        assert_eq!(diff.nodes.len(), 5);
+        assert_eq!(changes(&diff, 2), [Change::StatsChanged]);
+        assert_eq!(changes(&diff, 3), [Change::Moved]);
+        assert_eq!(changes(&diff, 4), [Change::Renamed]);
+        assert_eq!(changes(&diff, 6), [Change::Removed]);
+        assert_eq!(changes(&diff, 7), [Change::Added]);
+        assert!(diff
+            .nodes
+            .iter()
+            .all(|node| node.category == Category::Passive));
+
+        assert_eq!(diff.count(Change::Added), 1);
+        assert_eq!(diff.count(Change::IdChanged), 0);
+    }
+
+    #[test]
+    fn id_changed() {
+        use NodeKind::*;
+
+        let old = vec![
+            node(5, "Iron Will", Notable, (0, 0), "Iron Will"),
+            node(8, "Iron Grip", Notable, (100, 0), "Iron Grip"),
+            node(9, "Iron Reflexes", Keystone, (200, 0), "Iron Reflexes"),
+        ];
+        let new = vec![
+            // Same name and position.
+            node(50, "Iron Will", Notable, (0, 0), "Iron Will"),
+            // Same name, moved.
+            node(80, "Iron Grip", Notable, (150, 0), "Iron Grip"),
+            // Same position, renamed and changed stats.
+            node(90, "Iron Skin", Keystone, (200, 0), "Iron Skin"),
+        ];
+
+        let diff = diff(old, new);
+        assert_eq!(changes(&diff, 50), [Change::IdChanged]);
+        assert_eq!(changes(&diff, 80), [Change::Moved, Change::IdChanged]);
+        assert_eq!(
+            changes(&diff, 90),
+            [Change::Renamed, Change::IdChanged, Change::StatsChanged]
+        );
+        assert_eq!(diff.count(Change::IdChanged), 3);
+        assert_eq!(diff.count(Change::Added), 0);
+        assert_eq!(diff.count(Change::Removed), 0);
+    }
+
+    #[test]
+    fn categories() {
+        use NodeKind::*;
+
+        let old = vec![
+            node(
+                1,
+                "Resolute Technique",
+                Keystone,
+                (0, 0),
+                "Never deal Critical Strikes",
+            ),
+            node(2, "Rite of Ruin", berserker(), (5000, 5000), "Rite of Ruin"),
+            node(3, "Might", Notable, (100, 0), "Might"),
+        ];
+        let new = vec![
+            node(
+                1,
+                "Resolute Technique",
+                Keystone,
+                (0, 0),
+                "Your hits can't be Evaded",
+            ),
+            node(2, "Rite of Ruin", berserker(), (5100, 5000), "Rite of Ruin"),
+            // Nodes of another category are never matched.
+            node(4, "Might", Keystone, (100, 0), "Might"),
+        ];
+
+        let diff = diff(old, new);
+        let categories: Vec<_> = diff
+            .nodes
+            .iter()
+            .map(|node| (node.category.clone(), node.changes.clone()))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                (Category::Keystone, vec![Change::Added]),
+                (Category::Keystone, vec![Change::StatsChanged]),
+                (
+                    Category::Ascendancy("Berserker".to_owned()),
+                    vec![Change::Moved]
+                ),
+                (Category::Passive, vec![Change::Removed]),
+            ]
+        );
+
+        let markdown = diff.to_markdown();
+        assert!(markdown.contains("## Keystones"), "{markdown}");
+        assert!(markdown.contains("## Ascendancy: Berserker"), "{markdown}");
+        assert!(markdown.contains("## Passives"), "{markdown}");
+    }
+
+    #[test]
+    fn duplicate_names() {
+        use NodeKind::*;
+
+        let old = vec![
+            node(1, "Dexterity", Normal, (-5000, 0), "+10 to Dexterity"),
+            node(2, "Dexterity", Normal, (0, 2000), "+10 to Dexterity"),
+            node(3, "Dexterity", Normal, (0, 2100), "+10 to Dexterity"),
+        ];
+        let new = vec![
+            // The only other node with the name is across the tree.
+            node(11, "Dexterity", Normal, (5000, 0), "+10 to Dexterity"),
+            // The closer node with the name is matched.
+            node(13, "Dexterity", Normal, (0, 2090), "+10 to Dexterity"),
+        ];
+
+        let diff = diff(old, new);
+        assert_eq!(changes(&diff, 1), [Change::Removed]);
+        assert_eq!(changes(&diff, 2), [Change::Removed]);
+        assert_eq!(changes(&diff, 11), [Change::Added]);
+        assert_eq!(changes(&diff, 13), [Change::Moved, Change::IdChanged]);
+        let node = diff
+            .nodes
+            .iter()
+            .find(|node| node.new.as_ref().is_some_and(|new| new.id == 13));
+        assert_eq!(node.unwrap().old.as_ref().unwrap().id, 3);
+    }
+
+    #[test]
+    fn rename_and_move() {
+        use NodeKind::*;
+
+        // A node renamed in place is preferred over a node with the name nearby.
+        let old = vec![
+            node(1, "Strength", Normal, (0, 0), "+10 to Strength"),
+            node(2, "Dexterity", Normal, (100, 0), "+10 to Dexterity"),
+        ];
+        let new = vec![
+            node(10, "Dexterity", Normal, (0, 0), "+10 to Dexterity"),
+            node(20, "Intelligence", Normal, (100, 0), "+10 to Intelligence"),
+        ];
+
+        let diff = diff(old, new);
+        let pairs: Vec<_> = diff
+            .nodes
+            .iter()
+            .map(|node| (node.old.as_ref().unwrap().id, node.new.as_ref().unwrap().id))
+            .collect();
+        assert_eq!(pairs, [(1, 10), (2, 20)]);
+    }
+}
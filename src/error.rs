@@ -0,0 +1,85 @@
+use std::fmt;
+
+use crate::data::DataError;
+
+/// Result type of the library, see [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors of the library.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing failed.
+    Io(std::io::Error),
+    /// The tree export is not valid JSON or does not match the expected format.
+    Json(serde_json::Error),
+    /// A problem with the contents of the tree data.
+    Data(DataError),
+    /// A tree URL, build code or character export could not be decoded.
+    Decode(String),
+    /// An allocation cannot be encoded into a tree URL.
+    Encode(String),
+    /// A cluster jewel cannot be added to the tree.
+    Cluster(String),
+    /// No path to the planned nodes exists.
+    Plan(String),
+    /// Rasterizing a render failed.
+    #[cfg(feature = "png")]
+    Png(String),
+}
+
+impl Error {
+    /// Whether the error is caused by invalid user input, like a malformed build,
+    /// rather than by the tree data or the environment.
+    pub fn is_invalid_input(&self) -> bool {
+        matches!(
+            self,
+            Self::Decode(_) | Self::Encode(_) | Self::Cluster(_) | Self::Plan(_)
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Json(err) => write!(f, "invalid json: {err}"),
+            Self::Data(err) => write!(f, "{err}"),
+            Self::Decode(message)
+            | Self::Encode(message)
+            | Self::Cluster(message)
+            | Self::Plan(message) => write!(f, "{message}"),
+            #[cfg(feature = "png")]
+            Self::Png(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Json(err) => Some(err),
+            Self::Data(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<DataError> for Error {
+    fn from(err: DataError) -> Self {
+        Self::Data(err)
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::tree::{NodeKind, Tree};
+
+/// Undirected graph of the passive tree, see [`Graph::new`].
+#[derive(Debug, Clone)]
+pub struct Graph {
+    adjacency: BTreeMap<u32, BTreeSet<u32>>,
+    masteries: BTreeSet<u32>,
+    class_starts: BTreeMap<u8, u32>,
+}
+
+impl Graph {
+    /// Builds the graph from the nodes and connections of `tree`.
+    ///
+    /// Class starts are included and connected to the main tree only, ascendancies
+    /// are separate components reachable from their ascendancy start node.
+    /// Masteries are connected to the notables of their group, but paths never
+    /// pass through a mastery or the start of another class.
+    pub fn new(tree: &Tree) -> Self {
+        let mut adjacency: BTreeMap<_, BTreeSet<_>> = tree
+            .nodes
+            .iter()
+            .map(|node| (node.id, BTreeSet::new()))
+            .collect();
+
+        let mut connect = |a: u32, b: u32| {
+            adjacency.entry(a).or_default().insert(b);
+            adjacency.entry(b).or_default().insert(a);
+        };
+
+        for connection in &tree.connections {
+            connect(connection.a.id, connection.b.id);
+        }
+        for start in &tree.class_starts {
+            for &node in &start.connections {
+                connect(start.id, node);
+            }
+        }
+        for (&mastery, notables) in &tree.masteries {
+            for &notable in notables {
+                connect(mastery, notable);
+            }
+        }
+
+        for start in &tree.class_starts {
+            adjacency.entry(start.id).or_default();
+        }
+
+        Self {
+            adjacency,
+            masteries: tree
+                .nodes
+                .iter()
+                .filter(|node| node.kind == NodeKind::Mastery)
+                .map(|node| node.id)
+                .collect(),
+            class_starts: tree
+                .class_starts
+                .iter()
+                .map(|start| (start.class, start.id))
+                .collect(),
+        }
+    }
+
+    /// Whether the node is part of the graph.
+    pub fn contains(&self, id: u32) -> bool {
+        self.adjacency.contains_key(&id)
+    }
+
+    /// Nodes connected to the node, in either direction.
+    pub fn neighbours(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        self.adjacency.get(&id).into_iter().flatten().copied()
+    }
+
+    /// Node id of the start node of the class.
+    pub fn class_start(&self, class: u8) -> Option<u32> {
+        self.class_starts.get(&class).copied()
+    }
+
+    /// Whether the node is a mastery, masteries are never passed through.
+    pub fn is_mastery(&self, id: u32) -> bool {
+        self.masteries.contains(&id)
+    }
+
+    /// Whether the node is a class start, paths only pass through the class start they leave from.
+    pub fn is_class_start(&self, id: u32) -> bool {
+        self.class_starts.values().any(|&start| start == id)
+    }
+
+    /// Shortest path between two nodes, including both nodes.
+    pub fn shortest_path(&self, from: u32, to: u32) -> Option<Vec<u32>> {
+        self.shortest_path_from([from], to)
+    }
+
+    /// Shortest path from the closest of the `from` nodes to `to`.
+    ///
+    /// The path starts with the `from` node it leaves from and ends with `to`.
+    pub fn shortest_path_from(
+        &self,
+        from: impl IntoIterator<Item = u32>,
+        to: u32,
+    ) -> Option<Vec<u32>> {
+        let visited = self.search(from, Some(to));
+
+        let mut path = vec![to];
+        let mut current = visited.get(&to)?;
+        while let Some(parent) = current.parent {
+            path.push(parent);
+            current = &visited[&parent];
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    /// Number of connections from the closest of the `from` nodes to every reachable node.
+    pub fn distances(&self, from: impl IntoIterator<Item = u32>) -> BTreeMap<u32, u32> {
+        self.search(from, None)
+            .into_iter()
+            .map(|(id, visit)| (id, visit.distance))
+            .collect()
+    }
+
+    /// Nodes of `nodes` which are connected to `from` through `nodes` only, e.g.
+    /// the allocated nodes connected to the class start of a build.
+    pub fn connected(&self, from: u32, nodes: &BTreeSet<u32>) -> BTreeSet<u32> {
+        let mut connected = BTreeSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(id) = queue.pop_front() {
+            if id != from && (self.is_mastery(id) || self.is_class_start(id)) {
+                continue;
+            }
+
+            for neighbour in self.neighbours(id) {
+                if nodes.contains(&neighbour) && connected.insert(neighbour) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        connected.retain(|id| nodes.contains(id));
+        connected
+    }
+
+    /// Distances of every reachable node from each class start, keyed by class id.
+    pub fn class_distances(&self) -> BTreeMap<u8, BTreeMap<u32, u32>> {
+        self.class_starts
+            .iter()
+            .map(|(&class, &start)| (class, self.distances([start])))
+            .collect()
+    }
+
+    /// Breadth first search from all `from` nodes, stops early once `target` is reached.
+    fn search(
+        &self,
+        from: impl IntoIterator<Item = u32>,
+        target: Option<u32>,
+    ) -> BTreeMap<u32, Visit> {
+        let mut visited = BTreeMap::new();
+        let mut queue = VecDeque::new();
+
+        for id in from {
+            if self.contains(id) && !visited.contains_key(&id) {
+                visited.insert(
+                    id,
+                    Visit {
+                        distance: 0,
+                        parent: None,
+                    },
+                );
+                queue.push_back(id);
+            }
+        }
+
+        while let Some(id) = queue.pop_front() {
+            if Some(id) == target {
+                break;
+            }
+
+            let visit = visited[&id];
+            // Masteries and class starts cannot be passed through, unless the search starts there.
+            if visit.parent.is_some() && (self.is_mastery(id) || self.is_class_start(id)) {
+                continue;
+            }
+
+            for neighbour in self.neighbours(id) {
+                if visited.contains_key(&neighbour) {
+                    continue;
+                }
+
+                visited.insert(
+                    neighbour,
+                    Visit {
+                        distance: visit.distance + 1,
+                        parent: Some(id),
+                    },
+                );
+                queue.push_back(neighbour);
+            }
+        }
+
+        visited
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Visit {
+    distance: u32,
+    parent: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    /// The fixture tree with the Marauder start connected to the Berserker start,
+    /// as the class starts are in the official data, and a second notable next to
+    /// the mastery of the Scion group, which is only connected through the mastery.
+    fn graph() -> Graph {
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+        value["nodes"]["200"]["out"]
+            .as_array_mut()
+            .unwrap()
+            .push("1200".into());
+        value["nodes"]["1200"]["in"] = serde_json::json!(["200"]);
+
+        let mut notable = value["nodes"]["102"].clone();
+        notable["skill"] = 106.into();
+        notable["name"] = "Cleave".into();
+        notable["orbitIndex"] = 4.into();
+        notable["in"] = serde_json::json!([]);
+        value["nodes"]["106"] = notable;
+        value["groups"]["1"]["nodes"]
+            .as_array_mut()
+            .unwrap()
+            .push("106".into());
+
+        let data = data::Tree::new("test", &value.to_string()).unwrap();
+        Graph::new(&crate::tree::build(&data, &Default::default()).unwrap())
+    }
+
+    #[test]
+    fn distances() {
+        let graph = graph();
+
+        let distances = graph.distances([100]);
+        assert_eq!(
+            distances,
+            BTreeMap::from([
+                (100, 0),
+                (101, 1),
+                (102, 2),
+                (103, 2),
+                (201, 2),
+                (105, 3),
+                (200, 3),
+            ])
+        );
+
+        // The closest start is used.
+        let distances = graph.distances([100, 200]);
+        assert_eq!(distances[&201], 1);
+        assert_eq!(distances[&102], 2);
+    }
+
+    #[test]
+    fn class_distances() {
+        let graph = graph();
+
+        let distances = graph.class_distances();
+        assert_eq!(distances.keys().copied().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(distances[&0][&102], 2);
+        assert_eq!(distances[&1][&101], 2);
+        assert_eq!(distances[&1][&102], 3);
+        assert_eq!(distances[&1][&100], 3);
+    }
+
+    #[test]
+    fn class_start_to_ascendancy() {
+        let graph = graph();
+
+        assert!(!graph.neighbours(200).any(|id| id == 1200));
+        assert!(!graph.distances([200]).contains_key(&1200));
+        assert_eq!(graph.shortest_path(200, 1201), None);
+
+        // Ascendancies are reachable from their own start only.
+        assert_eq!(graph.shortest_path(1200, 1201), Some(vec![1200, 1201]));
+        assert_eq!(graph.shortest_path(1200, 200), None);
+    }
+
+    #[test]
+    fn masteries() {
+        let graph = graph();
+
+        assert!(graph.is_mastery(105));
+        assert_eq!(graph.neighbours(105).collect::<Vec<_>>(), [102, 106]);
+        // The mastery is reachable, but never part of a path to another node.
+        assert_eq!(
+            graph.shortest_path(100, 105),
+            Some(vec![100, 101, 102, 105])
+        );
+        assert_eq!(graph.shortest_path(100, 106), None);
+        assert_eq!(graph.shortest_path(106, 102), None);
+        assert!(!graph.distances([100]).contains_key(&106));
+
+        let nodes = BTreeSet::from([101, 102, 105, 106]);
+        assert_eq!(
+            graph.connected(100, &nodes),
+            BTreeSet::from([101, 102, 105])
+        );
+    }
+
+    #[test]
+    fn class_starts() {
+        let graph = graph();
+
+        assert_eq!(graph.class_start(0), Some(100));
+        assert_eq!(graph.class_start(1), Some(200));
+        assert_eq!(graph.class_start(2), None);
+
+        // Paths do not pass through the start of another class.
+        let nodes = BTreeSet::from([101, 200, 201]);
+        assert_eq!(
+            graph.connected(100, &nodes),
+            BTreeSet::from([101, 201, 200])
+        );
+        assert_eq!(
+            graph.shortest_path(100, 200),
+            Some(vec![100, 101, 201, 200])
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let graph = graph();
+
+        // The Warlock has no nodes besides its start, 30000 is a cluster notable
+        // without a group and 104 the unconnected node of a jewel socket.
+        assert_eq!(graph.shortest_path(100, 1400), None);
+        assert_eq!(graph.shortest_path(100, 104), None);
+        assert_eq!(graph.shortest_path(100, 30000), None);
+        assert_eq!(graph.shortest_path(100, 123456), None);
+        assert_eq!(graph.shortest_path(123456, 100), None);
+        assert_eq!(graph.distances([123456]), BTreeMap::new());
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::BufRead;
+
+use serde::{Deserialize, Serialize};
+
+use crate::allocation::Allocation;
+use crate::tree::{self, Tree};
+use crate::{Error, Result};
+
+/// Allocated nodes of a single build, as read by [`read`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Entry {
+    /// Tree version of the build, if known.
+    pub version: Option<String>,
+    pub nodes: BTreeSet<u32>,
+}
+
+/// A line of the JSONL input.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Url(String),
+    Nodes(Vec<u32>),
+    Object {
+        version: Option<String>,
+        url: Option<String>,
+        nodes: Option<Vec<u32>>,
+    },
+}
+
+/// Options to lay out trees for a heatmap.
+///
+/// Ascendancies keep their positions from the tree data, the heatmap shows
+/// the ascendancies of many builds at once.
+pub fn options() -> tree::Options {
+    tree::Options::new().ascendancy_layout(tree::AscendancyLayout::Original)
+}
+
+/// Reads builds from JSONL, one build per line.
+///
+/// A line is either a passive tree URL, a list of node ids or an object with
+/// a `url` or `nodes` and an optional tree `version`, e.g.
+/// `{"version": "3.25", "nodes": [1, 2, 3]}`. Empty lines are skipped.
+pub fn read(reader: impl BufRead) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = parse_line(&line)
+            .map_err(|err| Error::Decode(format!("invalid build on line {}: {err}", index + 1)))?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+fn parse_line(line: &str) -> Result<Entry> {
+    let (version, url, nodes) = match serde_json::from_str(line)? {
+        Line::Url(url) => (None, Some(url), None),
+        Line::Nodes(nodes) => (None, None, Some(nodes)),
+        Line::Object {
+            version,
+            url,
+            nodes,
+        } => (version, url, nodes),
+    };
+
+    let nodes = match (url, nodes) {
+        (Some(url), None) => {
+            let allocation = Allocation::from_url(&url)?;
+            allocation
+                .nodes
+                .iter()
+                .map(|&node| u32::from(node))
+                .chain(allocation.cluster_nodes)
+                .collect()
+        }
+        (None, Some(nodes)) => nodes.into_iter().collect(),
+        _ => {
+            return Err(Error::Decode(
+                "expected either a `url` or `nodes`".to_owned(),
+            ))
+        }
+    };
+
+    Ok(Entry { version, nodes })
+}
+
+/// How often nodes and connections are allocated by a set of builds.
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct Heatmap {
+    /// Number of builds.
+    pub builds: u32,
+    /// Number of builds allocating a node, keyed by node id.
+    pub nodes: BTreeMap<u32, u32>,
+    /// Number of builds allocating both nodes of a connection, keyed by the ordered node ids.
+    pub connections: BTreeMap<(u32, u32), u32>,
+}
+
+/// Allocation count of a node, see [`Heatmap::counts`].
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct NodeCount {
+    pub id: u32,
+    /// Name of the node, empty if the node is not part of the tree.
+    pub name: String,
+    pub count: u32,
+    /// Share of builds allocating the node.
+    pub frequency: f32,
+}
+
+impl Heatmap {
+    /// Counts the builds of `entries` on the tree `version`, laid out as `tree`.
+    ///
+    /// Builds without a version are counted on every tree.
+    pub fn for_version(tree: &Tree, version: &str, entries: &[Entry]) -> Self {
+        let mut heatmap = Self::default();
+        for entry in entries {
+            if entry.version.as_deref().is_none_or(|v| v == version) {
+                heatmap.add(tree, &entry.nodes);
+            }
+        }
+        heatmap
+    }
+
+    /// Counts the allocated `nodes` of a build on `tree`.
+    pub fn add(&mut self, tree: &Tree, nodes: &BTreeSet<u32>) {
+        self.builds += 1;
+
+        for &node in nodes {
+            *self.nodes.entry(node).or_default() += 1;
+        }
+        for connection in &tree.connections {
+            let (a, b) = (connection.a.id, connection.b.id);
+            if nodes.contains(&a) && nodes.contains(&b) {
+                *self.connections.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+    }
+
+    /// Largest allocation count of any node.
+    pub fn max(&self) -> u32 {
+        self.nodes.values().copied().max().unwrap_or(0)
+    }
+
+    /// Allocation counts of all allocated nodes, most allocated first.
+    pub fn counts(&self, tree: &Tree) -> Vec<NodeCount> {
+        let names: BTreeMap<_, _> = tree
+            .nodes
+            .iter()
+            .map(|node| (node.id, node.meta.name.as_str()))
+            .collect();
+
+        let mut counts: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(&id, &count)| NodeCount {
+                id,
+                name: names.get(&id).copied().unwrap_or_default().to_owned(),
+                count,
+                frequency: count as f32 / self.builds.max(1) as f32,
+            })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then(a.id.cmp(&b.id)));
+
+        counts
+    }
+
+    /// Allocation counts as CSV with the columns `id`, `name`, `count` and `frequency`.
+    pub fn to_csv(&self, tree: &Tree) -> String {
+        let mut output = String::from("id,name,count,frequency\n");
+        for count in self.counts(tree) {
+            let _ = writeln!(
+                output,
+                "{},\"{}\",{},{:.4}",
+                count.id,
+                count.name.replace('"', "\"\""),
+                count.count,
+                count.frequency
+            );
+        }
+        output
+    }
+
+    /// Allocation counts as JSON, including the number of builds.
+    pub fn to_json(&self, tree: &Tree) -> serde_json::Result<String> {
+        #[derive(Serialize)]
+        struct Counts {
+            builds: u32,
+            nodes: Vec<NodeCount>,
+        }
+
+        serde_json::to_string_pretty(&Counts {
+            builds: self.builds,
+            nodes: self.counts(tree),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    /// The fixture tree, `Might` is renamed to test quoting.
+    fn tree() -> Tree {
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+        value["nodes"]["102"]["name"] = r#"Might, the "Strong""#.into();
+        let data = data::Tree::new("test", &value.to_string()).unwrap();
+        tree::build(&data, &options()).unwrap()
+    }
+
+    fn entries(input: &str) -> Vec<Entry> {
+        read(input.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn read_lines() {
+        let allocation = Allocation {
+            class: 1,
+            nodes: vec![201, 101],
+            ..Default::default()
+        };
+        let url = allocation.to_url().unwrap();
+        let input = format!(
+            "\"{url}\"\n[101, 102]\n\n{{\"version\": \"3.25\", \"nodes\": [103]}}\n{{\"url\": \"{url}\"}}\n"
+        );
+
+        let entries = entries(&input);
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.version.as_deref(),
+                    Vec::from_iter(entry.nodes.clone()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (None, vec![101, 201]),
+                (None, vec![101, 102]),
+                (Some("3.25"), vec![103]),
+                (None, vec![101, 201]),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_invalid_lines() {
+        let invalid = [
+            "[101]\n\n{\"nodes\": [101]\n",
+            "[101]\n\n\"https://example.com\"\n",
+            "[101]\n\n{\"version\": \"3.25\"}\n",
+            "[101]\n\n[-1]\n",
+        ];
+
+        for input in invalid {
+            let err = read(input.as_bytes()).unwrap_err();
+            assert!(
+                err.is_invalid_input() || matches!(err, Error::Json(_)),
+                "{err}"
+            );
+            assert!(err.to_string().contains("on line 3"), "{err}");
+        }
+    }
+
+    #[test]
+    fn versions() {
+        let tree = tree();
+        let entries = entries(
+            r#"{"version": "3.24", "nodes": [101, 102]}
+            {"version": "3.25", "nodes": [101, 103]}
+            [101]"#,
+        );
+
+        let heatmap = Heatmap::for_version(&tree, "3.25", &entries);
+        assert_eq!(heatmap.builds, 2);
+        assert_eq!(heatmap.nodes, BTreeMap::from([(101, 2), (103, 1)]));
+        assert_eq!(heatmap.connections, BTreeMap::from([((101, 103), 1)]));
+        assert_eq!(heatmap.max(), 2);
+
+        let heatmap = Heatmap::for_version(&tree, "3.24", &entries);
+        assert_eq!(heatmap.builds, 2);
+        assert_eq!(heatmap.nodes, BTreeMap::from([(101, 2), (102, 1)]));
+
+        let heatmap = Heatmap::for_version(&tree, "3.23", &entries);
+        assert_eq!(heatmap.builds, 1);
+    }
+
+    #[test]
+    fn csv() {
+        let tree = tree();
+        let heatmap = Heatmap::for_version(&tree, "3.25", &entries("[101, 102]\n[101, 99999]"));
+
+        assert_eq!(
+            heatmap.to_csv(&tree),
+            "id,name,count,frequency\n\
+             101,\"Strength\",2,1.0000\n\
+             102,\"Might, the \"\"Strong\"\"\",1,0.5000\n\
+             99999,\"\",1,0.5000\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        let tree = tree();
+        let heatmap = Heatmap::for_version(&tree, "3.25", &entries("[101, 102]\n[101]"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&heatmap.to_json(&tree).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "builds": 2,
+                "nodes": [
+                    {"id": 101, "name": "Strength", "count": 2, "frequency": 1.0},
+                    {"id": 102, "name": "Might, the \"Strong\"", "count": 1, "frequency": 0.5},
+                ],
+            })
+        );
+    }
+}
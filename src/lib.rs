@@ -0,0 +1,63 @@
+//! Path of Exile tree minimap generator.
+//!
+//! Loads the passive tree export of a tree version with [`data::Tree::new`],
+//! lays it out for rendering with [`tree::build`] and renders the resulting
+//! [`tree::Tree`] with [`svg::render`] into a SVG template, which activates
+//! the nodes of a build at runtime.
+//!
+//! ```no_run
+//! # fn main() -> tmm::Result<()> {
+//! let json = std::fs::read_to_string("data.json")?;
+//!
+//! let data = tmm::data::Tree::new("3.25", &json)?;
+//! let options = tmm::tree::Options::new()
+//!     .ascendancy_layout(tmm::tree::AscendancyLayout::Grid);
+//! let tree = tmm::tree::build(&data, &options)?;
+//!
+//! let mut output = Vec::new();
+//! tmm::svg::render(&tree, &mut output)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Builds are decoded into an [`allocation::Allocation`] and can be baked
+//! into a static render with [`svg::render_build`].
+//!
+//! Fallible functions return an [`Error`].
+
+/// Passive tree allocations and the official passive tree URLs.
+pub mod allocation;
+/// Character passive skills from the official character window.
+pub mod character;
+/// Cluster jewel subgraphs.
+pub mod cluster;
+/// Comparison of two builds on one tree.
+pub mod compare;
+/// Tree data as exported by GGG.
+pub mod data;
+/// Changes between two tree versions.
+pub mod diff;
+mod error;
+/// Adjacency and shortest paths of the passive tree.
+pub mod graph;
+/// Allocation counts of many builds.
+pub mod heatmap;
+/// Planning the cheapest path to a set of nodes.
+pub mod plan;
+/// PNG rasterization of renders.
+#[cfg(feature = "png")]
+pub mod png;
+/// Path of Building build codes.
+pub mod pob;
+/// HTTP server for templates and rendered builds.
+#[cfg(feature = "serve")]
+pub mod serve;
+/// SVG rendering of trees and builds.
+pub mod svg;
+/// Trees laid out for rendering.
+pub mod tree;
+/// Validation of allocations against a tree version.
+pub mod validate;
+
+pub use data::DataError;
+pub use error::{Error, Result};
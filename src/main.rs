@@ -1,37 +1,895 @@
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use anyhow::{bail, Context};
 use bpaf::Bpaf;
 
+use tmm::allocation::Allocation;
+#[cfg(feature = "png")]
+use tmm::png;
+use tmm::{
+    character, cluster, compare, data, diff, graph, heatmap, plan, pob, svg, tree, validate,
+};
+
+mod cache;
 mod config;
-mod data;
-mod svg;
-mod tree;
+mod source;
 
 #[derive(Debug, Clone, Bpaf)]
 #[bpaf(options)]
-struct Args {
-    #[bpaf(fallback("config.toml".into()))]
+enum Command {
+    /// Write the template of every tree, optionally with a build.
+    #[bpaf(command)]
+    Generate {
+        #[bpaf(external(source))]
+        source: Source,
+        #[bpaf(external(build))]
+        build: Build,
+        /// Output directory, defaults to the `out` directory of the configuration.
+        #[bpaf(argument("DIR"))]
+        out: Option<PathBuf>,
+        #[bpaf(external(png))]
+        png: bool,
+        #[bpaf(external(size))]
+        size: Size,
+    },
+    /// Render a single tree, optionally with a build, as SVG or PNG.
+    #[bpaf(command)]
+    Render {
+        #[bpaf(external(source))]
+        source: Source,
+        #[bpaf(external(build))]
+        build: Build,
+        #[bpaf(external(size))]
+        size: Size,
+        /// Output file, rasterized into a PNG if it ends in `.png`.
+        #[bpaf(positional("OUTPUT"))]
+        output: PathBuf,
+    },
+    /// Look up nodes of a tree by id or name.
+    #[bpaf(command)]
+    Inspect {
+        #[bpaf(external(source))]
+        source: Source,
+        /// Node id or part of a node name.
+        #[bpaf(positional("QUERY"))]
+        query: String,
+    },
+    /// Compare the nodes of two trees, or the allocations of two builds on one tree.
+    #[bpaf(command)]
+    Diff {
+        #[bpaf(external(source))]
+        source: Source,
+        /// Passive tree URL, Path of Building export code or character passive skills
+        /// JSON file of a build to compare.
+        #[bpaf(argument("BUILD"))]
+        build: Vec<String>,
+        /// Report the changes between two trees as JSON instead of Markdown.
+        json: bool,
+        /// Additionally render the changes between two trees, or both builds, into an SVG.
+        #[bpaf(argument("FILE"))]
+        svg: Option<PathBuf>,
+    },
+    /// Check the tree data and report all problems.
+    #[bpaf(command)]
+    Check {
+        #[bpaf(external(source))]
+        source: Source,
+    },
+    /// Plan the fewest additional nodes to reach a set of nodes from a build.
+    #[bpaf(command)]
+    Plan {
+        #[bpaf(external(source))]
+        source: Source,
+        #[bpaf(external(build))]
+        build: Build,
+        #[bpaf(external(size))]
+        size: Size,
+        /// Render the build with the planned nodes highlighted, rasterized into a PNG
+        /// if it ends in `.png`.
+        #[bpaf(argument("FILE"))]
+        output: Option<PathBuf>,
+        /// Id or name of a node to reach.
+        #[bpaf(positional("NODE"))]
+        target: Vec<String>,
+    },
+    /// Render the allocation order of nodes or of a sequence of builds as an SVG.
+    #[bpaf(command)]
+    Timeline {
+        #[bpaf(external(source))]
+        source: Source,
+        /// Id or name of the next node to allocate.
+        #[bpaf(argument("NODE"))]
+        node: Vec<String>,
+        /// Passive tree URL, Path of Building export code or character passive skills
+        /// JSON file of the build at the next step.
+        #[bpaf(argument("BUILD"))]
+        build: Vec<String>,
+        /// Reveal the allocations step by step instead of numbering them.
+        animate: bool,
+        /// Duration of a step of the animation in seconds.
+        #[bpaf(argument("SECONDS"), fallback(0.5))]
+        step_duration: f32,
+        /// Output file.
+        #[bpaf(positional("OUTPUT"))]
+        output: PathBuf,
+    },
+    /// Count the allocated nodes of many builds and render them as a heatmap.
+    #[bpaf(command)]
+    Heatmap {
+        #[bpaf(external(source))]
+        source: Source,
+        /// Output directory, defaults to the `out` directory of the configuration.
+        #[bpaf(argument("DIR"))]
+        out: Option<PathBuf>,
+        #[bpaf(external(png))]
+        png: bool,
+        #[bpaf(external(size))]
+        size: Size,
+        /// JSONL file with a passive tree URL, a list of node ids or an object with
+        /// a `url` or `nodes` and an optional tree `version` per line.
+        #[bpaf(positional("FILE"))]
+        input: PathBuf,
+    },
+    /// Validate a build against a tree and report all violations.
+    #[bpaf(command)]
+    Validate {
+        #[bpaf(external(source))]
+        source: Source,
+        #[bpaf(external(build))]
+        build: Build,
+    },
+    /// Serve templates and rendered builds over HTTP, requires the `serve` feature.
+    #[bpaf(command)]
+    Serve {
+        #[bpaf(external(source))]
+        source: Source,
+        /// Address to listen on.
+        #[bpaf(argument("ADDR"), fallback("127.0.0.1:8000".to_owned()))]
+        listen: String,
+    },
+}
+
+/// Where to read the tree data from.
+///
+/// Without `--path` or `--url` trees are read from the configuration.
+#[derive(Debug, Clone, Bpaf)]
+struct Source {
+    /// Configuration file.
+    #[bpaf(argument("FILE"), fallback("config.toml".into()))]
     config: PathBuf,
+    /// Only read tree data from the cache, never download it.
+    offline: bool,
+    /// Name of a configured tree, all configured trees are used by default.
+    #[bpaf(argument("NAME"))]
+    tree: Vec<String>,
+    /// Read tree data from a local file instead.
+    #[bpaf(argument("PATH"))]
+    path: Vec<PathBuf>,
+    /// Download tree data from a URL instead.
+    #[bpaf(argument("URL"))]
+    url: Vec<String>,
+}
+
+#[derive(Debug, Clone, Bpaf)]
+struct Build {
+    /// Passive tree URL of a build to render.
+    #[bpaf(argument("URL"))]
+    build: Option<String>,
+    /// Path of Building export code of a build to render, only rendered on the
+    /// tree version of the build.
+    #[bpaf(argument("CODE"))]
+    pob: Option<String>,
+    /// Passive skills of a character to render, as returned by the `get-passive-skills`
+    /// endpoint of the official character window.
+    #[bpaf(argument("FILE"))]
+    character: Option<PathBuf>,
+}
+
+// Size options are only available with the `png` feature, see `png`.
+#[cfg(feature = "png")]
+#[derive(Debug, Clone, Bpaf)]
+struct Size {
+    /// Width of rasterized PNGs in pixels.
+    #[bpaf(argument("PX"))]
+    width: Option<u32>,
+    /// Height of rasterized PNGs in pixels.
+    #[bpaf(argument("PX"))]
+    height: Option<u32>,
+    /// Scale of rasterized PNGs relative to the tree's view box.
+    #[bpaf(argument("FACTOR"))]
+    scale: Option<f32>,
+}
+
+#[cfg(not(feature = "png"))]
+#[derive(Debug, Clone)]
+struct Size {}
+
+#[cfg(not(feature = "png"))]
+fn size() -> impl bpaf::Parser<Size> {
+    bpaf::pure(Size {})
+}
+
+/// Additionally rasterize every render into a PNG.
+#[cfg(feature = "png")]
+fn png() -> impl bpaf::Parser<bool> {
+    bpaf::long("png")
+        .help("Additionally rasterize every render into a PNG.")
+        .switch()
+}
+
+/// Without PNG support `--png` is rejected like any other unknown option.
+#[cfg(not(feature = "png"))]
+fn png() -> impl bpaf::Parser<bool> {
+    bpaf::pure(false)
 }
 
 fn main() -> anyhow::Result<()> {
-    let args = args().run();
+    match command().run() {
+        Command::Generate {
+            source,
+            build,
+            out,
+            png,
+            size,
+        } => generate(&source, &build, out, png.then_some(&size)),
+        Command::Render {
+            source,
+            build,
+            size,
+            output,
+        } => render(&source, &build, &size, &output),
+        Command::Inspect { source, query } => inspect(&source, &query),
+        Command::Diff {
+            source,
+            build,
+            json,
+            svg,
+        } => diff(&source, &build, json, svg.as_deref()),
+        Command::Check { source } => check(&source),
+        Command::Plan {
+            source,
+            build,
+            size,
+            output,
+            target,
+        } => plan(&source, &build, &size, output.as_deref(), &target),
+        Command::Timeline {
+            source,
+            node,
+            build,
+            animate,
+            step_duration,
+            output,
+        } => {
+            let style = if animate {
+                svg::TimelineStyle::Animated { step_duration }
+            } else {
+                svg::TimelineStyle::Numbered
+            };
+            timeline(&source, &node, &build, style, &output)
+        }
+        Command::Heatmap {
+            source,
+            out,
+            png,
+            size,
+            input,
+        } => heatmap(&source, out, png.then_some(&size), &input),
+        Command::Validate { source, build } => validate(&source, &build),
+        Command::Serve { source, listen } => serve(&source, &listen),
+    }
+}
 
-    let config = std::fs::read_to_string(args.config)?;
-    let config: config::Config = toml::from_str(&config)?;
+fn generate(
+    source: &Source,
+    build: &Build,
+    out: Option<PathBuf>,
+    png: Option<&Size>,
+) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let trees = source.trees(config.as_ref())?;
+    let out = out
+        .or_else(|| config.as_ref().map(|config| config.out.clone()))
+        .unwrap_or_else(|| "out".into());
+
+    let build = build.parse()?;
+    if let Some(name) = build.as_ref().and_then(ParsedBuild::tree_name) {
+        if !trees.iter().any(|tree| tree.name == name) {
+            bail!("build uses tree version {name} which is not selected");
+        }
+    }
 
-    for tree in config.tree {
-        let svg = format!("{}.svg", tree.name);
+    for tree in trees {
+        let name = tree.name.clone();
+        let svg = format!("{name}.svg");
         println!("--> {svg}");
 
-        let data = data::Tree::new(&tree.location.read()?)?;
-        let tree = tree::build(&data);
+        let (data, mut tree) = load(&tree, &cache)?;
 
-        let dest_path = config.out.join(&svg);
-        let mut output = File::create(dest_path)?;
+        let mut output = File::create(out.join(&svg))?;
         svg::render(&tree, &mut output)?;
+
+        if let Some(size) = png {
+            write_png(size, &out.join(format!("{name}.png")), &tree, None)?;
+        }
+
+        let build = build
+            .as_ref()
+            .filter(|build| build.tree_name().is_none_or(|only| only == name));
+
+        if let Some(build) = build {
+            let svg = format!("{name}.build.svg");
+            println!("--> {svg}");
+
+            let allocation = build.allocation(&data)?;
+            cluster::expand(&mut tree, &data, &allocation.jewels)?;
+            let build = svg::Build::from_allocation(&tree, &allocation);
+            let mut output = File::create(out.join(&svg))?;
+            svg::render_build(&tree, &build, &mut output)?;
+
+            if let Some(size) = png {
+                let dest_path = out.join(format!("{name}.build.png"));
+                write_png(size, &dest_path, &tree, Some(&build))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn render(source: &Source, build: &Build, size: &Size, output: &Path) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let build = build.parse()?;
+    let (data, mut tree) = load(&source.build_tree(config.as_ref(), build.as_ref())?, &cache)?;
+
+    let build = match build.map(|build| build.allocation(&data)).transpose()? {
+        Some(allocation) => {
+            cluster::expand(&mut tree, &data, &allocation.jewels)?;
+            Some(svg::Build::from_allocation(&tree, &allocation))
+        }
+        None => None,
+    };
+
+    write_render(size, output, &tree, build.as_ref())
+}
+
+/// Writes a render of `tree` to `output`, rasterized into a PNG if it ends in `.png`.
+fn write_render(
+    size: &Size,
+    output: &Path,
+    tree: &tree::Tree,
+    build: Option<&svg::Build>,
+) -> anyhow::Result<()> {
+    let is_png = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png {
+        return write_png(size, output, tree, build);
+    }
+
+    println!("--> {}", output.display());
+    let mut output = File::create(output)?;
+    match build {
+        Some(build) => svg::render_build(tree, build, &mut output)?,
+        None => svg::render(tree, &mut output)?,
+    }
+    Ok(())
+}
+
+fn inspect(source: &Source, query: &str) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let (_, tree) = load(&source.tree(config.as_ref())?, &cache)?;
+
+    let nodes = tree.search(query);
+    let graph = graph::Graph::new(&tree);
+    if nodes.is_empty() {
+        bail!("no node matches '{query}'");
+    }
+
+    for node in nodes {
+        println!(
+            "{} {} ({}) at {}, {}",
+            node.id,
+            node.meta.name,
+            node.kind.as_str(),
+            node.position.x,
+            node.position.y
+        );
+        for stat in &node.meta.stats {
+            println!("    {}", stat.replace('\n', "\n    "));
+        }
+
+        let connected = graph
+            .neighbours(node.id)
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        if !connected.is_empty() {
+            println!("    connected to {}", connected.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn diff(
+    source: &Source,
+    builds: &[String],
+    json: bool,
+    image: Option<&Path>,
+) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let trees = source.trees(config.as_ref())?;
+
+    match (trees.as_slice(), builds) {
+        ([old, new], []) => {
+            let old = load_data(old, &cache)?;
+            let new = load_data(new, &cache)?;
+
+            // Both trees are laid out the same way, regardless of their configuration.
+            let diff = diff::diff(&old, &new)?;
+            if json {
+                println!("{}", diff.to_json()?);
+            } else {
+                print!("{}", diff.to_markdown());
+            }
+
+            if let Some(image) = image {
+                eprintln!("--> {}", image.display());
+                let mut output = File::create(image)?;
+                svg::render_diff(&old, &new, &diff, &mut output)?;
+            }
+        }
+        ([tree], [a, b]) => {
+            let (data, mut tree) = load(tree, &cache)?;
+            let a = parse_build(a, &data)?;
+            let b = parse_build(b, &data)?;
+            cluster::expand_all(&mut tree, &data, &[&a.jewels, &b.jewels])?;
+
+            print!("{}", compare::compare(&tree, &a, &b));
+
+            if let Some(image) = image {
+                let a = svg::Build::from_allocation(&tree, &a);
+                let b = svg::Build::from_allocation(&tree, &b);
+
+                eprintln!("--> {}", image.display());
+                let mut output = File::create(image)?;
+                svg::render_comparison(&tree, &a, &b, &mut output)?;
+            }
+        }
+        _ => bail!("diff requires either two trees, or one tree and two builds"),
+    }
+
+    Ok(())
+}
+
+fn check(source: &Source) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+
+    let mut total = 0;
+    for tree in source.trees(config.as_ref())? {
+        println!("--> {}", tree.name);
+
+        let data = load_data(&tree, &cache)?;
+        let problems = data.check();
+        for problem in &problems {
+            println!("{problem}");
+        }
+        total += problems.len();
+    }
+
+    if total > 0 {
+        bail!("found {total} problems in the tree data");
     }
 
     Ok(())
 }
+
+fn plan(
+    source: &Source,
+    build: &Build,
+    size: &Size,
+    output: Option<&Path>,
+    targets: &[String],
+) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let Some(build) = build.parse()? else {
+        bail!("plan requires a build, pass it with --build, --pob or --character");
+    };
+    let (data, mut tree) = load(&source.build_tree(config.as_ref(), Some(&build))?, &cache)?;
+
+    let mut allocation = build.allocation(&data)?;
+    cluster::expand(&mut tree, &data, &allocation.jewels)?;
+
+    let targets = targets
+        .iter()
+        .map(|target| plan::resolve(&tree, target))
+        .collect::<tmm::Result<Vec<_>>>()?;
+    let plan = plan::plan(&tree, &allocation, &targets)?;
+
+    for &id in &plan.nodes {
+        let name = tree.node(id).map_or("<unknown>", |node| &node.meta.name);
+        println!("+ {id} {name}");
+    }
+    println!("{} additional points", plan.nodes.len());
+
+    plan.apply(&mut allocation);
+    println!("{}", allocation.to_url()?);
+
+    if let Some(output) = output {
+        let mut build = svg::Build::from_allocation(&tree, &allocation);
+        build.highlighted = plan.nodes.iter().copied().collect();
+        write_render(size, output, &tree, Some(&build))?;
+    }
+
+    Ok(())
+}
+
+fn timeline(
+    source: &Source,
+    nodes: &[String],
+    builds: &[String],
+    style: svg::TimelineStyle,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let (data, tree) = load(&source.tree(config.as_ref())?, &cache)?;
+
+    let timeline = match (nodes, builds) {
+        ([], []) => bail!("timeline requires either nodes or builds"),
+        (nodes, []) => {
+            let nodes = nodes
+                .iter()
+                .map(|node| plan::resolve(&tree, node))
+                .collect::<tmm::Result<Vec<_>>>()?;
+            svg::Timeline::from_nodes(&tree, nodes)
+        }
+        ([], builds) => {
+            let builds = builds
+                .iter()
+                .map(|build| {
+                    let allocation = parse_build(build, &data)?;
+                    Ok(svg::Build::from_allocation(&tree, &allocation))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            svg::Timeline::from_builds(builds)
+        }
+        _ => bail!("--node and --build cannot be used together"),
+    };
+
+    println!("--> {}", output.display());
+    let mut output = File::create(output)?;
+    svg::render_timeline(&tree, &timeline, style, &mut output)?;
+    Ok(())
+}
+
+fn heatmap(
+    source: &Source,
+    out: Option<PathBuf>,
+    png: Option<&Size>,
+    input: &Path,
+) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let trees = source.trees(config.as_ref())?;
+    let out = out
+        .or_else(|| config.as_ref().map(|config| config.out.clone()))
+        .unwrap_or_else(|| "out".into());
+
+    let file = File::open(input).with_context(|| format!("failed to read {}", input.display()))?;
+    let entries = heatmap::read(std::io::BufReader::new(file))?;
+
+    for entry in &entries {
+        match &entry.version {
+            Some(version) if !trees.iter().any(|tree| tree.name == *version) => {
+                bail!("build uses tree version {version} which is not selected")
+            }
+            None if trees.len() != 1 => {
+                bail!("builds without a version require a single tree, select one with --tree, --path or --url")
+            }
+            _ => {}
+        }
+    }
+
+    for tree in trees {
+        let name = tree.name.clone();
+        let (data, tree) = load(&tree, &cache)?;
+
+        let heatmap = heatmap::Heatmap::for_version(&tree, &name, &entries);
+        if heatmap.builds == 0 {
+            continue;
+        }
+
+        let svg = format!("{name}.heatmap.svg");
+        println!("--> {svg}");
+        let mut render = Vec::new();
+        svg::render_heatmap(&data, &heatmap, &mut render)?;
+        std::fs::write(out.join(&svg), &render)?;
+
+        if let Some(size) = png {
+            write_rasterized(size, &out.join(format!("{name}.heatmap.png")), &render)?;
+        }
+
+        let csv = format!("{name}.heatmap.csv");
+        println!("--> {csv}");
+        std::fs::write(out.join(csv), heatmap.to_csv(&tree))?;
+
+        let json = format!("{name}.heatmap.json");
+        println!("--> {json}");
+        std::fs::write(out.join(json), heatmap.to_json(&tree)?)?;
+    }
+
+    Ok(())
+}
+
+fn validate(source: &Source, build: &Build) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let Some(build) = build.parse()? else {
+        bail!("validate requires a build, pass it with --build, --pob or --character");
+    };
+    let (data, mut tree) = load(&source.build_tree(config.as_ref(), Some(&build))?, &cache)?;
+
+    let allocation = build.allocation(&data)?;
+    cluster::expand(&mut tree, &data, &allocation.jewels)?;
+
+    let report = validate::validate(&data, &tree, &allocation);
+    print!("{report}");
+
+    if !report.is_valid() {
+        bail!("found {} violations in the build", report.violations.len());
+    }
+
+    Ok(())
+}
+
+impl Source {
+    /// Reads the configuration, it is optional if trees are passed with `--path` or `--url`.
+    fn config(&self) -> anyhow::Result<Option<config::Config>> {
+        let direct = !self.path.is_empty() || !self.url.is_empty();
+        if direct && !self.config.exists() {
+            return Ok(None);
+        }
+
+        let config = std::fs::read_to_string(&self.config)
+            .with_context(|| format!("failed to read {}", self.config.display()))?;
+        Ok(Some(toml::from_str(&config)?))
+    }
+
+    fn cache(&self, config: Option<&config::Config>) -> cache::Cache {
+        let dir = config.and_then(|config| config.cache.clone());
+        cache::Cache::new(dir, self.offline)
+    }
+
+    /// All selected trees, all configured trees if none are selected.
+    fn trees(&self, config: Option<&config::Config>) -> anyhow::Result<Vec<config::Tree>> {
+        let direct = |source: &str, location| config::Tree {
+            name: source_name(source),
+            sha256: None,
+            options: Default::default(),
+            location,
+        };
+
+        let mut trees = Vec::new();
+        for path in &self.path {
+            let location = config::Location::Path(path.clone());
+            trees.push(direct(&path.to_string_lossy(), location));
+        }
+        for url in &self.url {
+            trees.push(direct(url, config::Location::Url(url.clone())));
+        }
+
+        if let Some(config) = config {
+            for name in &self.tree {
+                let tree = config
+                    .tree(name)
+                    .with_context(|| format!("tree {name} is not configured"))?;
+                trees.push(tree.clone());
+            }
+
+            if trees.is_empty() {
+                trees = config.tree.clone();
+            }
+        }
+
+        Ok(trees)
+    }
+
+    /// The single selected tree.
+    fn tree(&self, config: Option<&config::Config>) -> anyhow::Result<config::Tree> {
+        let mut trees = self.trees(config)?;
+        if trees.len() != 1 {
+            bail!("expected a single tree, select one with --tree, --path or --url");
+        }
+        Ok(trees.remove(0))
+    }
+
+    /// The tree of `build`, for builds which know their tree version.
+    ///
+    /// Without a selected tree the configured tree of that version is used,
+    /// a selected tree must be of the version of the build.
+    fn build_tree(
+        &self,
+        config: Option<&config::Config>,
+        build: Option<&ParsedBuild>,
+    ) -> anyhow::Result<config::Tree> {
+        let Some(name) = build.and_then(ParsedBuild::tree_name) else {
+            return self.tree(config);
+        };
+
+        let selected = !self.tree.is_empty() || !self.path.is_empty() || !self.url.is_empty();
+        if let (false, Some(config)) = (selected, config) {
+            return config.tree(&name).cloned().with_context(|| {
+                format!("build uses tree version {name} which is not configured")
+            });
+        }
+
+        let tree = self.tree(config)?;
+        if tree.name != name {
+            bail!(
+                "build uses tree version {name}, but tree {} is selected",
+                tree.name
+            );
+        }
+        Ok(tree)
+    }
+}
+
+impl Build {
+    /// Parses the build to render, `None` if there is none.
+    fn parse(&self) -> anyhow::Result<Option<ParsedBuild>> {
+        Ok(match (&self.build, &self.pob, &self.character) {
+            (Some(url), None, None) => Some(ParsedBuild::Allocation(Allocation::from_url(url)?)),
+            (None, Some(code), None) => {
+                let build = pob::Build::from_code(code)?;
+                Some(ParsedBuild::Pob(build))
+            }
+            (None, None, Some(path)) => Some(ParsedBuild::Character(read_character(path)?)),
+            (None, None, None) => None,
+            _ => bail!("only one of --build, --pob and --character can be used"),
+        })
+    }
+}
+
+/// A build passed on the command line, see [`Build::parse`].
+enum ParsedBuild {
+    Allocation(Allocation),
+    Pob(pob::Build),
+    /// Jewel sockets of characters can only be resolved with the tree data.
+    Character(character::Passives),
+}
+
+impl ParsedBuild {
+    /// Name of the tree the build belongs to, if known.
+    fn tree_name(&self) -> Option<String> {
+        match self {
+            Self::Pob(build) => Some(build.tree_name()),
+            Self::Allocation(_) | Self::Character(_) => None,
+        }
+    }
+
+    fn allocation(&self, data: &data::Tree) -> anyhow::Result<Allocation> {
+        match self {
+            Self::Allocation(allocation) => Ok(allocation.clone()),
+            Self::Pob(build) => Ok(build.allocation.clone()),
+            Self::Character(passives) => Ok(passives.allocation(data)?),
+        }
+    }
+}
+
+fn read_character(path: &Path) -> anyhow::Result<character::Passives> {
+    let passives = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(character::Passives::new(&passives)?)
+}
+
+fn load(tree: &config::Tree, cache: &cache::Cache) -> anyhow::Result<(data::Tree, tree::Tree)> {
+    let data = load_data(tree, cache)?;
+    let tree = tree::build(&data, &tree.options)?;
+    Ok((data, tree))
+}
+
+fn load_data(tree: &config::Tree, cache: &cache::Cache) -> anyhow::Result<data::Tree> {
+    Ok(data::Tree::new(&tree.name, &tree.read(cache)?)?)
+}
+
+/// Parses a passive tree URL, a Path of Building export code or the path of a
+/// character's passive skills JSON file, see [`pob::parse`].
+fn parse_build(build: &str, data: &data::Tree) -> anyhow::Result<Allocation> {
+    let path = Path::new(build);
+    if path.extension().is_some_and(|ext| ext == "json") && path.is_file() {
+        return Ok(read_character(path)?.allocation(data)?);
+    }
+
+    let (_, allocation) = pob::parse(build)?;
+    Ok(allocation)
+}
+
+/// Name of a tree passed as a path or URL, e.g. `3.25.0` for `.../3.25.0/data.json`.
+fn source_name(source: &str) -> String {
+    let mut segments = source
+        .split(['/', '\\'])
+        .filter(|segment| !segment.is_empty())
+        .rev();
+    let file = segments.next().unwrap_or(source);
+    let stem = file.split('.').next().unwrap_or(file);
+
+    match segments.next() {
+        Some(parent) if stem == "data" => parent.to_owned(),
+        _ => stem.to_owned(),
+    }
+}
+
+#[cfg(feature = "serve")]
+fn serve(source: &Source, listen: &str) -> anyhow::Result<()> {
+    let config = source.config()?;
+    let cache = source.cache(config.as_ref());
+    let trees = source
+        .trees(config.as_ref())?
+        .iter()
+        .map(|tree| load(tree, &cache))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let app = tmm::serve::App::new(trees)?;
+    println!("--> http://{listen}");
+    app.serve(listen)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve(_: &Source, _: &str) -> anyhow::Result<()> {
+    bail!("tmm was built without HTTP server support, enable the `serve` feature")
+}
+
+#[cfg(feature = "png")]
+fn write_png(
+    size: &Size,
+    dest_path: &Path,
+    tree: &tree::Tree,
+    build: Option<&svg::Build>,
+) -> anyhow::Result<()> {
+    println!("--> {}", dest_path.display());
+
+    let mut output = File::create(dest_path)?;
+    png::render(tree, build, size.to_png(), &mut output)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "png"))]
+fn write_png(_: &Size, _: &Path, _: &tree::Tree, _: Option<&svg::Build>) -> anyhow::Result<()> {
+    bail!("tmm was built without PNG support, enable the `png` feature")
+}
+
+/// Rasterizes an already rendered SVG into a PNG.
+#[cfg(feature = "png")]
+fn write_rasterized(size: &Size, dest_path: &Path, svg: &[u8]) -> anyhow::Result<()> {
+    println!("--> {}", dest_path.display());
+
+    std::fs::write(dest_path, png::rasterize(svg, size.to_png())?)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "png"))]
+fn write_rasterized(_: &Size, _: &Path, _: &[u8]) -> anyhow::Result<()> {
+    bail!("tmm was built without PNG support, enable the `png` feature")
+}
+
+#[cfg(feature = "png")]
+impl Size {
+    fn to_png(&self) -> png::Size {
+        match (self.width, self.height, self.scale) {
+            (_, _, Some(scale)) => png::Size::Scale(scale),
+            (Some(width), Some(height), None) => png::Size::Fit { width, height },
+            (Some(width), None, None) => png::Size::Width(width),
+            (None, Some(height), None) => png::Size::Height(height),
+            (None, None, None) => png::Size::default(),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeSet;
+
+use crate::allocation::Allocation;
+use crate::graph::Graph;
+use crate::tree::{AscendancyNodeKind, NodeKind, Tree};
+use crate::{Error, Result};
+
+/// Additional nodes to allocate to reach a set of target nodes, see [`plan`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Plan {
+    /// Additional nodes, in the order they can be allocated.
+    pub nodes: Vec<u32>,
+    /// The target nodes, including targets which are already allocated.
+    pub targets: Vec<u32>,
+}
+
+impl Plan {
+    /// Adds the planned nodes to `allocation`.
+    pub fn apply(&self, allocation: &mut Allocation) {
+        for &node in &self.nodes {
+            match u16::try_from(node) {
+                Ok(node) => allocation.nodes.push(node),
+                Err(_) => allocation.cluster_nodes.push(node),
+            }
+        }
+    }
+}
+
+/// Finds the fewest additional nodes which connect all `targets` to `allocation`.
+///
+/// This is the node weighted Steiner tree problem, which is solved heuristically:
+/// the closest unreached target is connected by its shortest path until all targets
+/// are reached. Only allocated nodes connected to the class or ascendancy start
+/// are used as a starting point.
+pub fn plan(tree: &Tree, allocation: &Allocation, targets: &[u32]) -> Result<Plan> {
+    let graph = Graph::new(tree);
+
+    let class_start = graph
+        .class_start(allocation.class)
+        .ok_or_else(|| Error::Plan(format!("class {} does not exist", allocation.class)))?;
+    let ascendancy_start = tree
+        .ascendancy(allocation.class, allocation.ascendancy)
+        .into_iter()
+        .chain(tree.alternate_ascendancy(allocation.class, allocation.alternate_ascendancy))
+        .filter_map(|ascendancy| {
+            tree.nodes.iter().find(|node| {
+                matches!(
+                    &node.kind,
+                    NodeKind::Ascendancy { kind: AscendancyNodeKind::Start, ascendancy: a } if *a == ascendancy
+                )
+            })
+        })
+        .map(|node| node.id);
+
+    let allocated: BTreeSet<_> = allocation
+        .nodes
+        .iter()
+        .map(|&node| u32::from(node))
+        .chain(allocation.cluster_nodes.iter().copied())
+        .collect();
+
+    let mut reached = BTreeSet::new();
+    for start in std::iter::once(class_start).chain(ascendancy_start) {
+        reached.insert(start);
+        reached.extend(graph.connected(start, &allocated));
+    }
+
+    let mut nodes = Vec::new();
+    let mut remaining: BTreeSet<_> = targets
+        .iter()
+        .copied()
+        .filter(|target| !reached.contains(target))
+        .collect();
+
+    while !remaining.is_empty() {
+        // Paths cannot continue from a mastery, even if it is allocated.
+        let sources = reached.iter().copied().filter(|&id| !graph.is_mastery(id));
+
+        let distances = graph.distances(sources.clone());
+        let path = remaining
+            .iter()
+            .filter(|target| distances.contains_key(target))
+            .min_by_key(|target| distances[target])
+            .and_then(|&target| graph.shortest_path_from(sources, target));
+        let Some(path) = path else {
+            let unreachable: Vec<_> = remaining.iter().map(u32::to_string).collect();
+            return Err(Error::Plan(format!(
+                "nodes {} cannot be reached from the class or ascendancy start",
+                unreachable.join(", ")
+            )));
+        };
+
+        for node in path {
+            if !reached.insert(node) {
+                continue;
+            }
+            // The path can run into allocated nodes which were not connected before,
+            // they connect everything allocated behind them as well.
+            if allocated.contains(&node) {
+                reached.extend(graph.connected(node, &allocated));
+            } else {
+                nodes.push(node);
+            }
+        }
+        remaining.retain(|target| !reached.contains(target));
+    }
+
+    Ok(Plan {
+        nodes,
+        targets: targets.to_vec(),
+    })
+}
+
+/// Looks up a node by its id or name.
+///
+/// Names are matched case insensitively and must be unique, as many small
+/// passives share their name, e.g. `Strength`.
+pub fn resolve(tree: &Tree, query: &str) -> Result<u32> {
+    if let Ok(id) = query.parse::<u32>() {
+        if tree.nodes.iter().any(|node| node.id == id) {
+            return Ok(id);
+        }
+        return Err(Error::Plan(format!("node {id} does not exist")));
+    }
+
+    let matches: Vec<_> = tree
+        .nodes
+        .iter()
+        .filter(|node| node.meta.name.eq_ignore_ascii_case(query))
+        .collect();
+
+    match matches.as_slice() {
+        [] => Err(Error::Plan(format!("no node is named '{query}'"))),
+        [node] => Ok(node.id),
+        nodes => {
+            let ids: Vec<_> = nodes.iter().map(|node| node.id.to_string()).collect();
+            Err(Error::Plan(format!(
+                "'{query}' is ambiguous, pass one of the node ids {}",
+                ids.join(", ")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data;
+
+    /// The fixture tree with a second `Strength` node after `Might`.
+    fn tree() -> Tree {
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+        let mut node = value["nodes"]["101"].clone();
+        node["skill"] = 106.into();
+        node["orbitIndex"] = 4.into();
+        node["in"] = serde_json::json!(["102"]);
+        node["out"] = serde_json::json!([]);
+        value["nodes"]["106"] = node;
+        value["nodes"]["102"]["out"] = serde_json::json!(["106"]);
+        value["groups"]["1"]["nodes"]
+            .as_array_mut()
+            .unwrap()
+            .push("106".into());
+
+        let data = data::Tree::new("test", &value.to_string()).unwrap();
+        crate::tree::build(&data, &Default::default()).unwrap()
+    }
+
+    fn scion(nodes: &[u16]) -> Allocation {
+        Allocation {
+            class: 0,
+            ascendancy: 1,
+            nodes: nodes.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn path() {
+        let tree = tree();
+
+        let plan = plan(&tree, &scion(&[]), &[106]).unwrap();
+        assert_eq!(plan.nodes, [101, 102, 106]);
+        assert_eq!(plan.targets, [106]);
+
+        let mut allocation = scion(&[]);
+        plan.apply(&mut allocation);
+        assert_eq!(allocation.nodes, [101, 102, 106]);
+    }
+
+    #[test]
+    fn allocated() {
+        let tree = tree();
+
+        let plan = plan(&tree, &scion(&[101, 102]), &[102, 101]).unwrap();
+        assert_eq!(plan.nodes, Vec::<u32>::new());
+        assert_eq!(plan.targets, [102, 101]);
+
+        // Allocated nodes which are not connected to the class start are not used
+        // as a starting point, but they are not allocated again.
+        let plan = super::plan(&tree, &scion(&[102]), &[106]).unwrap();
+        assert_eq!(plan.nodes, [101, 106]);
+
+        // Connecting them also reaches the nodes allocated behind them.
+        let plan = super::plan(&tree, &scion(&[102, 106]), &[106, 103]).unwrap();
+        assert_eq!(plan.nodes, [101, 103]);
+    }
+
+    #[test]
+    fn shared_path() {
+        let tree = tree();
+
+        let plan = plan(&tree, &scion(&[]), &[103, 102, 201]).unwrap();
+        assert_eq!(plan.nodes.len(), 4);
+        assert_eq!(plan.nodes[0], 101);
+        assert_eq!(
+            plan.nodes.iter().copied().collect::<BTreeSet<_>>(),
+            BTreeSet::from([101, 102, 103, 201])
+        );
+    }
+
+    #[test]
+    fn ascendancy() {
+        let tree = tree();
+
+        let allocation = Allocation {
+            class: 1,
+            ascendancy: 2,
+            ..Default::default()
+        };
+        let plan = plan(&tree, &allocation, &[1201, 201]).unwrap();
+        assert_eq!(plan.nodes, [201, 1201]);
+    }
+
+    #[test]
+    fn unreachable() {
+        let tree = tree();
+
+        // Nodes of another ascendancy, a mastery is never passed through and the
+        // cluster notable is not connected to anything.
+        for target in [1201, 30000, 123456] {
+            let err = plan(&tree, &scion(&[]), &[102, target]).unwrap_err();
+            assert!(err.is_invalid_input(), "{err}");
+            assert!(err.to_string().contains(&target.to_string()), "{err}");
+        }
+
+        let allocation = Allocation {
+            class: 7,
+            ..Default::default()
+        };
+        assert!(plan(&tree, &allocation, &[102]).is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let tree = tree();
+
+        assert_eq!(super::resolve(&tree, "102").unwrap(), 102);
+        assert_eq!(super::resolve(&tree, "might").unwrap(), 102);
+        assert_eq!(super::resolve(&tree, "Rite of Ruin").unwrap(), 1201);
+
+        let err = super::resolve(&tree, "Strength").unwrap_err();
+        assert!(err.to_string().contains("101, 106"), "{err}");
+        assert!(super::resolve(&tree, "Nothing").is_err());
+        assert!(super::resolve(&tree, "123456").is_err());
+    }
+}
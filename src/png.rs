@@ -0,0 +1,131 @@
+use std::io::Write;
+
+use resvg::{tiny_skia, usvg};
+
+use crate::svg::{self, Build};
+use crate::tree::Tree;
+use crate::{Error, Result};
+
+/// Output size of a rasterized tree.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Size {
+    /// Fixed width, the height follows the aspect ratio of the tree.
+    Width(u32),
+    /// Fixed height, the width follows the aspect ratio of the tree.
+    Height(u32),
+    /// Largest size fitting into the box while keeping the aspect ratio.
+    Fit { width: u32, height: u32 },
+    /// Scale factor relative to the tree's view box.
+    Scale(f32),
+}
+
+impl Default for Size {
+    fn default() -> Self {
+        Self::Width(2048)
+    }
+}
+
+/// Renders `tree` as a PNG, optionally with a `build` baked in.
+///
+/// The image is rasterized from the output of [`svg::render_build`],
+/// without a build only the unallocated tree is shown.
+pub fn render(
+    tree: &Tree,
+    build: Option<&Build>,
+    size: Size,
+    output: &mut dyn Write,
+) -> Result<()> {
+    let mut svg = Vec::new();
+    svg::render_build(tree, build.unwrap_or(&Build::default()), &mut svg)?;
+
+    let png = rasterize(&svg, size)?;
+    output.write_all(&png)?;
+
+    Ok(())
+}
+
+/// Rasterizes an SVG document into a PNG.
+pub fn rasterize(svg: &[u8], size: Size) -> Result<Vec<u8>> {
+    let tree = usvg::Tree::from_data(svg, &usvg::Options::default())
+        .map_err(|err| Error::Png(format!("failed to parse svg: {err}")))?;
+
+    let tree_size = tree.size();
+    let scale = match size {
+        Size::Width(width) => width as f32 / tree_size.width(),
+        Size::Height(height) => height as f32 / tree_size.height(),
+        Size::Fit { width, height } => {
+            (width as f32 / tree_size.width()).min(height as f32 / tree_size.height())
+        }
+        Size::Scale(scale) => scale,
+    };
+
+    let width = (tree_size.width() * scale).round() as u32;
+    let height = (tree_size.height() * scale).round() as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| Error::Png(format!("invalid image size {width}x{height}")))?;
+
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap
+        .encode_png()
+        .map_err(|err| Error::Png(format!("failed to encode png: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{data, heatmap, tree};
+
+    fn data() -> data::Tree {
+        data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap()
+    }
+
+    /// Rows of the image containing pixels of the label color.
+    fn label_rows(png: &[u8]) -> BTreeSet<u32> {
+        let pixmap = tiny_skia::Pixmap::decode_png(png).unwrap();
+        pixmap
+            .pixels()
+            .iter()
+            .enumerate()
+            .filter(|(_, pixel)| pixel.red() > 200 && pixel.green() > 200 && pixel.blue() > 200)
+            .map(|(i, _)| i as u32 / pixmap.width())
+            .collect()
+    }
+
+    #[test]
+    fn heatmap_legend() {
+        let data = data();
+        let tree = tree::build(&data, &heatmap::options()).unwrap();
+        let mut heatmap = heatmap::Heatmap::default();
+        heatmap.add(&tree, &BTreeSet::from([101, 102]));
+        heatmap.add(&tree, &BTreeSet::from([101]));
+
+        let mut render = Vec::new();
+        svg::render_heatmap(&data, &heatmap, &mut render).unwrap();
+        let png = rasterize(&render, Size::Width(1000)).unwrap();
+        let height = tiny_skia::Pixmap::decode_png(&png).unwrap().height();
+
+        // Only the labels of the legend at the bottom use the label color.
+        let rows = label_rows(&png);
+        assert!(!rows.is_empty());
+        assert!(rows.iter().all(|&row| row > height * 9 / 10), "{rows:?}");
+    }
+
+    #[test]
+    fn timeline_steps() {
+        let tree = tree::build(&data(), &Default::default()).unwrap();
+        let timeline = svg::Timeline::from_nodes(&tree, [101, 102]);
+
+        let mut render = Vec::new();
+        svg::render_timeline(&tree, &timeline, svg::TimelineStyle::Numbered, &mut render).unwrap();
+        let png = rasterize(&render, Size::Scale(1.0)).unwrap();
+
+        assert!(!label_rows(&png).is_empty());
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use base64::Engine;
+
+use crate::allocation::{Allocation, Jewel, URL_SAFE};
+use crate::{Error, Result};
+
+/// Item properties in the item text which are not modifiers.
+const ITEM_PROPERTIES: &[&str] = &[
+    "Unique ID:",
+    "Item Level:",
+    "LevelReq:",
+    "Implicits:",
+    "Radius:",
+    "Limited to:",
+    "Quality:",
+    "Sockets:",
+];
+
+/// The active passive tree spec of a Path of Building export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Build {
+    /// Tree version as used by Path of Building, e.g. `3_25`.
+    pub tree_version: String,
+    pub allocation: Allocation,
+}
+
+impl Build {
+    /// Decodes a Path of Building export code.
+    ///
+    /// Export codes are URL safe base64 of the zlib compressed build XML.
+    pub fn from_code(code: &str) -> Result<Self> {
+        let data = URL_SAFE
+            .decode(code.trim())
+            .map_err(|_| invalid("build code is not valid base64"))?;
+
+        let mut xml = String::new();
+        flate2::read::ZlibDecoder::new(data.as_slice())
+            .read_to_string(&mut xml)
+            .map_err(|_| invalid("build code is not a valid zlib stream"))?;
+
+        Self::from_xml(&xml)
+    }
+
+    /// Parses the build XML of a Path of Building export.
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let doc = roxmltree::Document::parse(xml)
+            .map_err(|err| invalid(format!("build code contains invalid xml: {err}")))?;
+
+        let tree = doc
+            .descendants()
+            .find(|node| node.has_tag_name("Tree"))
+            .ok_or_else(|| invalid("build does not contain a passive tree"))?;
+
+        // `activeSpec` is 1-based.
+        let active_spec = parse_attr(&tree, "activeSpec")?.unwrap_or(1usize);
+        let Some(spec) = tree
+            .children()
+            .filter(|node| node.has_tag_name("Spec"))
+            .nth(active_spec.saturating_sub(1))
+        else {
+            return Err(invalid(format!("active spec {active_spec} does not exist")));
+        };
+
+        let tree_version = spec
+            .attribute("treeVersion")
+            .ok_or_else(|| invalid("spec is missing the tree version"))?
+            .to_owned();
+
+        let mut allocation = Allocation {
+            class: parse_attr(&spec, "classId")?.unwrap_or(0),
+            ascendancy: parse_attr(&spec, "ascendClassId")?.unwrap_or(0),
+            alternate_ascendancy: parse_attr(&spec, "secondaryAscendClassId")?.unwrap_or(0),
+            ..Default::default()
+        };
+
+        let nodes = spec.attribute("nodes").unwrap_or_default();
+        for node in nodes.split(',').filter(|node| !node.is_empty()) {
+            let node: u32 = node
+                .parse()
+                .map_err(|_| invalid(format!("invalid node id '{node}'")))?;
+            match u16::try_from(node) {
+                Ok(node) => allocation.nodes.push(node),
+                Err(_) => allocation.cluster_nodes.push(node),
+            }
+        }
+
+        // Formatted as `{node,effect},{node,effect}`.
+        let effects = spec.attribute("masteryEffects").unwrap_or_default();
+        let effects = effects.trim_start_matches('{').trim_end_matches('}');
+        for effect in effects.split("},{").filter(|effect| !effect.is_empty()) {
+            let Some((node, effect)) = effect.split_once(',') else {
+                return Err(invalid(format!("invalid mastery effect '{effect}'")));
+            };
+            let node = node
+                .trim()
+                .parse()
+                .map_err(|_| invalid(format!("invalid mastery node id '{node}'")))?;
+            let effect = effect
+                .trim()
+                .parse()
+                .map_err(|_| invalid(format!("invalid mastery effect id '{effect}'")))?;
+            allocation.mastery_effects.insert(node, effect);
+        }
+
+        let items: BTreeMap<u32, &str> = doc
+            .descendants()
+            .filter(|node| node.has_tag_name("Item"))
+            .filter_map(|node| Some((node.attribute("id")?.parse().ok()?, node.text()?)))
+            .collect();
+
+        for socket in spec
+            .descendants()
+            .filter(|node| node.has_tag_name("Socket"))
+        {
+            let node = parse_attr(&socket, "nodeId")?
+                .ok_or_else(|| invalid("socket is missing the node id"))?;
+            let item_id = parse_attr(&socket, "itemId")?.unwrap_or(0);
+            // Empty sockets are exported with an item id of 0.
+            if item_id == 0 {
+                continue;
+            }
+
+            // Sockets can refer to items which are not part of the export.
+            if let Some(item) = items.get(&item_id) {
+                allocation.jewels.insert(node, parse_jewel(item));
+            }
+        }
+
+        Ok(Self {
+            tree_version,
+            allocation,
+        })
+    }
+
+    /// Name of the tree version as used in the tmm configuration, e.g. `3.25`.
+    ///
+    /// Variants of a tree version keep their suffix, e.g. `3_25_ruthless` is named
+    /// `3.25_ruthless`, they are different trees and need their own configuration entry.
+    pub fn tree_name(&self) -> String {
+        let mut parts = self.tree_version.split('_').peekable();
+
+        let mut name = Vec::new();
+        while let Some(part) = parts.next_if(|part| part.chars().all(|c| c.is_ascii_digit())) {
+            name.push(part);
+        }
+        let mut name = name.join(".");
+
+        for part in parts {
+            name.push('_');
+            name.push_str(part);
+        }
+
+        name
+    }
+}
+
+/// Parses a passive tree URL or a Path of Building export code.
+///
+/// Bare tree URL payloads are accepted as well, URLs are tried first as export
+/// codes never decode into a valid tree URL.
+/// Export codes also return the [`Build::tree_name`] of the build.
+pub fn parse(build: &str) -> Result<(Option<String>, Allocation)> {
+    match Allocation::from_url(build) {
+        Ok(allocation) => Ok((None, allocation)),
+        Err(err) if build.starts_with("http") || build.contains("passive-skill-tree") => Err(err),
+        Err(_) => {
+            let build = Build::from_code(build)?;
+            Ok((Some(build.tree_name()), build.allocation))
+        }
+    }
+}
+
+/// Parses the item text of a jewel.
+///
+/// The base type is the first line ending in `Jewel`, all following lines
+/// which are not item properties are modifiers.
+fn parse_jewel(item: &str) -> Jewel {
+    let mut lines = item
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("Rarity:"));
+
+    let base_type = lines
+        .by_ref()
+        .find(|line| line.ends_with("Jewel"))
+        .unwrap_or_default()
+        .to_owned();
+
+    let mods = lines
+        .filter(|line| !ITEM_PROPERTIES.iter().any(|p| line.starts_with(p)))
+        // Strip modifier tags, like `{crafted}` or `{range:0.5}`.
+        .map(|line| {
+            let mut line = line;
+            while let Some((_, rest)) = line.strip_prefix('{').and_then(|l| l.split_once('}')) {
+                line = rest;
+            }
+            line.to_owned()
+        })
+        .collect();
+
+    Jewel {
+        base_type,
+        mods,
+        ..Default::default()
+    }
+}
+
+fn parse_attr<T>(node: &roxmltree::Node<'_, '_>, name: &str) -> Result<Option<T>>
+where
+    T: std::str::FromStr,
+{
+    node.attribute(name)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid(format!("invalid value '{value}' for attribute {name}")))
+        })
+        .transpose()
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::Decode(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const XML: &str = include_str!("../tests/fixtures/build.xml");
+
+    fn code(xml: &str) -> String {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+        URL_SAFE.encode(encoder.finish().unwrap())
+    }
+
+    fn build(version: &str) -> Build {
+        Build {
+            tree_version: version.to_owned(),
+            allocation: Allocation::default(),
+        }
+    }
+
+    #[test]
+    fn tree_name() {
+        assert_eq!(build("3_25").tree_name(), "3.25");
+        assert_eq!(build("3_10").tree_name(), "3.10");
+        assert_eq!(build("3_25_ruthless").tree_name(), "3.25_ruthless");
+        assert_eq!(build("3_25_alternate").tree_name(), "3.25_alternate");
+    }
+
+    #[test]
+    fn active_spec() {
+        let build = Build::from_xml(XML).unwrap();
+
+        assert_eq!(build.tree_version, "3_25");
+        assert_eq!(build.tree_name(), "3.25");
+        assert_eq!(build.allocation.class, 1);
+        assert_eq!(build.allocation.ascendancy, 2);
+        assert_eq!(build.allocation.alternate_ascendancy, 3);
+
+        // Without `activeSpec` the first spec is used.
+        let xml = XML.replace(r#"activeSpec="2""#, "");
+        let build = Build::from_xml(&xml).unwrap();
+        assert_eq!(build.tree_version, "3_24");
+        assert_eq!(build.allocation.class, 0);
+        assert_eq!(build.allocation.nodes, [100, 101]);
+        assert!(build.allocation.cluster_nodes.is_empty());
+        assert!(build.allocation.jewels.is_empty());
+    }
+
+    #[test]
+    fn cluster_nodes() {
+        let build = Build::from_xml(XML).unwrap();
+
+        assert_eq!(build.allocation.nodes, [200, 201, 103, 105, 1200, 1201]);
+        assert_eq!(build.allocation.cluster_nodes, [65572, 65573]);
+    }
+
+    #[test]
+    fn mastery_effects() {
+        let build = Build::from_xml(XML).unwrap();
+
+        let effects: Vec<_> = build.allocation.mastery_effects.into_iter().collect();
+        assert_eq!(effects, [(105, 48385), (106, 12)]);
+    }
+
+    #[test]
+    fn sockets() {
+        let build = Build::from_xml(XML).unwrap();
+
+        // Empty sockets and sockets of items which are not exported are skipped.
+        assert_eq!(build.allocation.jewels.len(), 1);
+
+        let jewel = &build.allocation.jewels[&103];
+        assert_eq!(jewel.base_type, "Large Cluster Jewel");
+        assert_eq!(
+            jewel.mods,
+            [
+                "Adds 8 Passive Skills",
+                "2 Added Passive Skills are Jewel Sockets",
+                "Added Small Passive Skills grant: 12% increased Fire Damage",
+                "1 Added Passive Skill is Prismatic Heart",
+            ]
+        );
+        assert!(jewel.nodes.is_empty());
+    }
+
+    #[test]
+    fn from_code() {
+        assert_eq!(
+            Build::from_code(&code(XML)).unwrap(),
+            Build::from_xml(XML).unwrap()
+        );
+        // Codes copied from a text field often end in a newline.
+        assert_eq!(
+            Build::from_code(&format!("{}\n", code(XML))).unwrap(),
+            Build::from_xml(XML).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_code() {
+        let invalid = [
+            "not base64!".to_owned(),
+            URL_SAFE.encode(b"not zlib"),
+            code("<PathOfBuilding>"),
+            code("<PathOfBuilding/>"),
+        ];
+
+        for code in invalid {
+            let err = Build::from_code(&code).unwrap_err();
+            assert!(err.is_invalid_input(), "{code}: {err}");
+        }
+    }
+
+    #[test]
+    fn parse_url_or_code() {
+        let allocation = Allocation {
+            version: 6,
+            class: 1,
+            nodes: vec![101, 201],
+            ..Default::default()
+        };
+        let url = allocation.to_url().unwrap();
+        let payload = url.rsplit('/').next().unwrap();
+        let build = Build::from_xml(XML).unwrap();
+
+        assert_eq!(parse(&url).unwrap(), (None, allocation.clone()));
+        assert_eq!(parse(payload).unwrap(), (None, allocation));
+        assert_eq!(
+            parse(&code(XML)).unwrap(),
+            (Some("3.25".to_owned()), build.allocation)
+        );
+
+        // Invalid URLs are not mistaken for export codes.
+        let err = parse(&format!("{url}AAAA")).unwrap_err();
+        assert!(err.to_string().contains("tree url"), "{err}");
+        assert!(parse("not a build").unwrap_err().is_invalid_input());
+    }
+
+    #[test]
+    fn invalid_xml() {
+        let invalid = [
+            XML.replace(r#"activeSpec="2""#, r#"activeSpec="3""#),
+            XML.replace(r#"activeSpec="2""#, r#"activeSpec="two""#),
+            XML.replace(r#"treeVersion="3_25""#, ""),
+            XML.replace("65573", "65573x"),
+            XML.replace("{106,12}", "{106}"),
+            XML.replace(r#"nodeId="103""#, ""),
+            XML.replace("</Tree>", ""),
+        ];
+
+        for xml in invalid {
+            let err = Build::from_xml(&xml).unwrap_err();
+            assert!(err.is_invalid_input(), "{err}");
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::allocation::Allocation;
+use crate::{cluster, data, pob, svg, tree};
+
+/// Renders of a build are immutable for a tree version, cache them for a year.
+const CACHE_CONTROL_BUILD: &str = "public, max-age=31536000, immutable";
+/// Templates change with the tree data of a version, clients revalidate them using the ETag.
+const CACHE_CONTROL_TEMPLATE: &str = "public, no-cache";
+
+/// Serves templates and rendered builds of all loaded trees.
+///
+/// * `/{version}.svg`: the template of the tree version.
+/// * `/{version}/{build}.svg`: a static render of the build.
+/// * `/{version}/{build}.png`: a rasterized render of the build, requires the `png` feature.
+///
+/// Builds are either the payload of a passive tree URL or a Path of Building export code.
+/// Export codes are rendered on the tree version of the build, regardless of `{version}`.
+pub struct App {
+    trees: BTreeMap<String, Version>,
+}
+
+struct Version {
+    data: data::Tree,
+    tree: tree::Tree,
+    template: Vec<u8>,
+    /// ETag of the template, renders of builds derive their ETag from it.
+    etag: String,
+}
+
+/// Response to a request, see [`App::handle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub etag: Option<String>,
+    pub cache_control: Option<&'static str>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            etag: None,
+            cache_control: None,
+            body,
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            etag: None,
+            cache_control: None,
+            body: message.into().into_bytes(),
+        }
+    }
+
+    /// Responds with `304 Not Modified` if `if_none_match` matches `etag`,
+    /// otherwise with the response created by `f`.
+    ///
+    /// The ETag is checked first, cached responses are never rendered again.
+    fn cached(
+        etag: String,
+        cache_control: &'static str,
+        if_none_match: Option<&str>,
+        f: impl FnOnce() -> Self,
+    ) -> Self {
+        let not_modified = if_none_match
+            .is_some_and(|if_none_match| if_none_match.split(',').any(|tag| tag.trim() == etag));
+        // Not modified responses have no body and therefore no content type.
+        let response = if not_modified {
+            Self {
+                status: 304,
+                ..Self::ok("", Vec::new())
+            }
+        } else {
+            f()
+        };
+
+        match response.status {
+            200 | 304 => Self {
+                etag: Some(etag),
+                cache_control: Some(cache_control),
+                ..response
+            },
+            _ => response,
+        }
+    }
+}
+
+impl App {
+    /// Creates the app from laid out trees, the templates are rendered upfront.
+    ///
+    /// Trees are served under their [`data::Tree::version`].
+    pub fn new(trees: impl IntoIterator<Item = (data::Tree, tree::Tree)>) -> crate::Result<Self> {
+        let trees = trees
+            .into_iter()
+            .map(|(data, tree)| {
+                let mut template = Vec::new();
+                svg::render(&tree, &mut template)?;
+
+                let etag = etag(&[data.version.as_bytes(), &template]);
+                let version = Version {
+                    data,
+                    tree,
+                    template,
+                    etag,
+                };
+                Ok((version.data.version.clone(), version))
+            })
+            .collect::<crate::Result<_>>()?;
+
+        Ok(Self { trees })
+    }
+
+    /// Handles a request for `path`.
+    ///
+    /// Responds with `304 Not Modified` if `if_none_match` matches the ETag of the response.
+    pub fn handle(&self, path: &str, if_none_match: Option<&str>) -> Response {
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let path = path.trim_start_matches('/');
+
+        let (version, build) = match path.split_once('/') {
+            Some((version, build)) => (version, Some(build)),
+            None => match path.strip_suffix(".svg") {
+                Some(version) => (version, None),
+                None => return Response::error(404, "not found"),
+            },
+        };
+        let Some(version) = self.trees.get(version) else {
+            return Response::error(404, format!("unknown tree version {version}"));
+        };
+
+        match build {
+            Some(build) => self.handle_build(version, build, if_none_match),
+            None => Response::cached(
+                version.etag.clone(),
+                CACHE_CONTROL_TEMPLATE,
+                if_none_match,
+                || Response::ok("image/svg+xml", version.template.clone()),
+            ),
+        }
+    }
+
+    /// Serves the app on `addr` until the server fails.
+    pub fn serve(&self, addr: &str) -> crate::Result<()> {
+        let server = tiny_http::Server::http(addr)
+            .map_err(|err| std::io::Error::other(format!("failed to listen on {addr}: {err}")))?;
+        self.run(&server);
+
+        Ok(())
+    }
+
+    fn run(&self, server: &tiny_http::Server) {
+        for request in server.incoming_requests() {
+            let if_none_match = request
+                .headers()
+                .iter()
+                .find(|header| header.field.equiv("If-None-Match"))
+                .map(|header| header.value.to_string());
+
+            let response = self.handle(request.url(), if_none_match.as_deref());
+
+            let mut headers = Vec::new();
+            if !response.content_type.is_empty() {
+                headers.push(header("Content-Type", response.content_type));
+            }
+            if let Some(etag) = &response.etag {
+                headers.push(header("ETag", etag));
+            }
+            if let Some(cache_control) = response.cache_control {
+                headers.push(header("Cache-Control", cache_control));
+            }
+
+            let response = tiny_http::Response::new(
+                response.status.into(),
+                headers,
+                response.body.as_slice(),
+                Some(response.body.len()),
+                None,
+            );
+            if let Err(err) = request.respond(response) {
+                eprintln!("failed to respond: {err}");
+            }
+        }
+    }
+
+    fn handle_build(&self, version: &Version, path: &str, if_none_match: Option<&str>) -> Response {
+        let (build, extension) = path.rsplit_once('.').unwrap_or((path, ""));
+        let content_type = match extension {
+            "svg" => "image/svg+xml",
+            "png" if cfg!(feature = "png") => "image/png",
+            "png" => return Response::error(501, "tmm was built without PNG support"),
+            _ => return Response::error(404, "not found"),
+        };
+
+        let (version, allocation) = match pob::parse(build) {
+            Ok((None, allocation)) => (version, allocation),
+            Ok((Some(name), allocation)) => match self.trees.get(&name) {
+                Some(version) => (version, allocation),
+                None => {
+                    return Response::error(
+                        400,
+                        format!("build uses tree version {name} which is not served"),
+                    )
+                }
+            },
+            Err(err) => return Response::error(400, format!("invalid build: {err}")),
+        };
+
+        // Renders only depend on the build, the tree version and the renderer.
+        let etag = etag(&[
+            env!("CARGO_PKG_VERSION").as_bytes(),
+            version.etag.as_bytes(),
+            path.as_bytes(),
+        ]);
+        Response::cached(etag, CACHE_CONTROL_BUILD, if_none_match, || {
+            let result = version.render(&allocation);
+            #[cfg(feature = "png")]
+            let result = match extension {
+                "png" => result.and_then(|svg| crate::png::rasterize(&svg, Default::default())),
+                _ => result,
+            };
+
+            match result {
+                Ok(body) => Response::ok(content_type, body),
+                Err(err) if err.is_invalid_input() => {
+                    Response::error(400, format!("invalid build: {err}"))
+                }
+                Err(err) => Response::error(500, format!("failed to render build: {err}")),
+            }
+        })
+    }
+}
+
+impl Version {
+    /// Renders `allocation`, the tree is only copied to add cluster jewels to it.
+    fn render(&self, allocation: &Allocation) -> crate::Result<Vec<u8>> {
+        let tree = if allocation.jewels.values().any(cluster::is_cluster) {
+            let mut tree = self.tree.clone();
+            cluster::expand(&mut tree, &self.data, &allocation.jewels)?;
+            Cow::Owned(tree)
+        } else {
+            Cow::Borrowed(&self.tree)
+        };
+        let build = svg::Build::from_allocation(&tree, allocation);
+
+        let mut output = Vec::new();
+        svg::render_build(&tree, &build, &mut output)?;
+        Ok(output)
+    }
+}
+
+fn header(field: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(field, value).expect("header should be valid")
+}
+
+/// Quoted ETag of `parts`, parts are length prefixed to keep them apart.
+fn etag(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    let hash: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("\"{hash}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use base64::Engine;
+
+    use super::*;
+    use crate::allocation::URL_SAFE;
+
+    /// The fixture tree served as versions 3.24 and 3.25, the Marauder group moved in 3.24.
+    fn app() -> App {
+        let trees = ["3.24", "3.25"].map(|version| {
+            let mut value: serde_json::Value =
+                serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+            if version == "3.24" {
+                value["groups"]["2"]["x"] = 2000.into();
+            }
+            let data = data::Tree::new(version, &value.to_string()).unwrap();
+            let tree = tree::build(&data, &Default::default()).unwrap();
+            (data, tree)
+        });
+        App::new(trees).unwrap()
+    }
+
+    /// Serves a new app on a random port, returns the base URL.
+    fn serve() -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || app().run(&server));
+        format!("http://{addr}")
+    }
+
+    fn build() -> String {
+        let allocation = Allocation {
+            class: 1,
+            nodes: vec![201],
+            ..Default::default()
+        };
+        URL_SAFE.encode(allocation.encode().unwrap())
+    }
+
+    /// Path of Building code of a Scion build.
+    fn build_pob(tree_version: &str) -> String {
+        code(&format!(
+            r#"<PathOfBuilding>
+                <Tree activeSpec="1">
+                    <Spec treeVersion="{tree_version}" classId="0" nodes="101,102" />
+                </Tree>
+            </PathOfBuilding>"#
+        ))
+    }
+
+    /// Path of Building code of a build with a cluster jewel in a normal passive.
+    fn build_invalid_cluster() -> String {
+        code(
+            r#"<PathOfBuilding>
+            <Tree activeSpec="1">
+                <Spec treeVersion="3_25" classId="1" nodes="201">
+                    <Sockets><Socket nodeId="201" itemId="1" /></Sockets>
+                </Spec>
+            </Tree>
+            <Items><Item id="1">Rarity: MAGIC
+Large Cluster Jewel
+Adds 8 Passive Skills</Item></Items>
+        </PathOfBuilding>"#,
+        )
+    }
+
+    fn code(xml: &str) -> String {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+        URL_SAFE.encode(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn template() {
+        let base = serve();
+
+        let response = ureq::get(&format!("{base}/3.25.svg")).call().unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), "image/svg+xml");
+        assert_eq!(
+            response.header("Cache-Control"),
+            Some(CACHE_CONTROL_TEMPLATE)
+        );
+        let etag = response.header("ETag").unwrap().to_owned();
+
+        let response = ureq::get(&format!("{base}/3.25.svg"))
+            .set("If-None-Match", &etag)
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.header("ETag"), Some(etag.as_str()));
+        assert_eq!(response.into_string().unwrap(), "");
+    }
+
+    #[test]
+    fn render_build() {
+        let base = serve();
+        let url = format!("{base}/3.25/{}.svg", build());
+
+        let response = ureq::get(&url).call().unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.content_type(), "image/svg+xml");
+        assert_eq!(response.header("Cache-Control"), Some(CACHE_CONTROL_BUILD));
+        let etag = response.header("ETag").unwrap().to_owned();
+        assert!(response.into_string().unwrap().starts_with("<svg"));
+
+        let response = ureq::get(&url)
+            .set("If-None-Match", &format!("\"other\", {etag}"))
+            .call()
+            .unwrap();
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.header("Cache-Control"), Some(CACHE_CONTROL_BUILD));
+    }
+
+    #[test]
+    fn errors() {
+        let base = serve();
+        let status = |path: &str| match ureq::get(&format!("{base}{path}")).call() {
+            Ok(response) => response.status(),
+            Err(ureq::Error::Status(status, response)) => {
+                assert_eq!(response.content_type(), "text/plain");
+                assert_eq!(response.header("ETag"), None);
+                status
+            }
+            Err(err) => panic!("{err}"),
+        };
+
+        assert_eq!(status("/unknown.svg"), 404);
+        assert_eq!(status("/3.25/AAAA.txt"), 404);
+        assert_eq!(status("/3.25/AAAA!.svg"), 400);
+        assert_eq!(
+            status(&format!("/3.25/{}.svg", build_invalid_cluster())),
+            400
+        );
+    }
+
+    #[test]
+    fn not_modified_before_render() {
+        let app = app();
+        let path = "/3.25/invalid.svg";
+
+        // Invalid builds are never cached and there is nothing to revalidate.
+        let response = app.handle(path, None);
+        assert_eq!(response.status, 400);
+        assert_eq!(response.etag, None);
+
+        let response = app.handle(&format!("/3.25/{}.svg", build_invalid_cluster()), None);
+        assert_eq!(response.status, 400);
+        let body = String::from_utf8(response.body).unwrap();
+        assert!(body.contains("does not accept cluster jewels"), "{body}");
+
+        let path = format!("/3.25/{}.svg", build());
+        let etag = app.handle(&path, None).etag.unwrap();
+        let response = app.handle(&path, Some(&etag));
+        assert_eq!(response.status, 304);
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn pob_tree_version() {
+        let app = app();
+        let render = |version: &str, build: &str| {
+            let response = app.handle(&format!("/{version}/{build}.svg"), None);
+            (response.status, String::from_utf8(response.body).unwrap())
+        };
+
+        // Export codes are rendered on the tree version of the build.
+        let (status, body) = render("3.25", &build_pob("3_24"));
+        assert_eq!(status, 200, "{body}");
+        assert!(body.contains(r#"cx="2000""#), "{body}");
+        assert_eq!(render("3.24", &build_pob("3_24")), (status, body));
+
+        let (status, body) = render("3.24", &build_pob("3_25"));
+        assert_eq!(status, 200, "{body}");
+        assert!(body.contains(r#"cx="1000""#), "{body}");
+
+        let (status, body) = render("3.25", &build_pob("3_26"));
+        assert_eq!(status, 400);
+        assert_eq!(body, "build uses tree version 3.26 which is not served");
+
+        // Tree URLs do not know their version.
+        assert_eq!(render("3.24", &build()).0, 200);
+    }
+}
@@ -0,0 +1,223 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context};
+
+/// Reads a local file, gzip (`.gz`) and zstd (`.zst`) compressed files are decompressed.
+pub fn read_path(path: &Path) -> anyhow::Result<String> {
+    let mut data = String::new();
+    decompress(path)?
+        .read_to_string(&mut data)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(data)
+}
+
+/// Reads `file` from a local zip or tar archive.
+///
+/// Tar archives may be gzip or zstd compressed, the archive type is taken from
+/// the file extension. Like in the archives GitHub
+/// creates of a repository, a top level directory in the archive is ignored.
+pub fn read_archive(path: &Path, file: &str) -> anyhow::Result<String> {
+    let mut data = String::new();
+
+    if has_extension(path, &["zip"]) {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)
+            .with_context(|| format!("{} is not a valid zip archive", path.display()))?;
+
+        let Some(name) = archive
+            .file_names()
+            .find(|name| is_archive_file(name, file))
+            .map(str::to_owned)
+        else {
+            bail!("{} does not contain {file}", path.display());
+        };
+
+        archive.by_name(&name)?.read_to_string(&mut data)?;
+    } else if has_extension(path, &["tar", "gz", "tgz", "zst", "tzst"]) {
+        let mut archive = tar::Archive::new(decompress(path)?);
+
+        let mut entries = archive.entries()?;
+        let mut entry = loop {
+            let Some(entry) = entries.next() else {
+                bail!("{} does not contain {file}", path.display());
+            };
+            let entry = entry?;
+            if is_archive_file(&entry.path()?.to_string_lossy(), file) {
+                break entry;
+            }
+        };
+
+        entry.read_to_string(&mut data)?;
+    } else {
+        bail!("{} is not a zip or tar archive", path.display());
+    }
+
+    Ok(data)
+}
+
+/// Reads `file` at revision `rev` from a local git repository.
+///
+/// Revisions starting with `-` are rejected, git would parse them as options.
+pub fn read_git(repo: &Path, rev: &str, file: &str) -> anyhow::Result<String> {
+    if rev.starts_with('-') {
+        bail!("invalid git revision {rev}, revisions must not start with `-`");
+    }
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("show")
+        .arg(format!("{rev}:{file}"))
+        .output()
+        .context("failed to run git")?;
+
+    if !output.status.success() {
+        bail!(
+            "failed to read {file} at {rev} from {}: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    String::from_utf8(output.stdout).with_context(|| format!("{file} at {rev} is not valid utf-8"))
+}
+
+fn decompress(path: &Path) -> anyhow::Result<Box<dyn Read>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+
+    Ok(if has_extension(path, &["gz", "tgz"]) {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else if has_extension(path, &["zst", "tzst"]) {
+        Box::new(zstd::Decoder::new(file)?)
+    } else {
+        Box::new(file)
+    })
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+fn is_archive_file(name: &str, file: &str) -> bool {
+    let name = name.trim_start_matches("./");
+    name == file || name.split_once('/').is_some_and(|(_, name)| name == file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const DATA: &str = r#"{"tree": "Default"}"#;
+
+    /// Fresh temporary directory for the test `name`.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tmm-source-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Tar archive containing `data.json` in a top level directory, like GitHub archives.
+    fn tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(DATA.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                "skilltree-export-3.25.0/data.json",
+                DATA.as_bytes(),
+            )
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn compressed() {
+        let dir = dir("compressed");
+
+        let files = [
+            ("data.json", DATA.as_bytes().to_vec()),
+            ("data.json.gz", gzip(DATA.as_bytes())),
+            (
+                "data.json.zst",
+                zstd::encode_all(DATA.as_bytes(), 0).unwrap(),
+            ),
+        ];
+        for (name, data) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, data).unwrap();
+            assert_eq!(read_path(&path).unwrap(), DATA, "{name}");
+        }
+
+        let err = read_path(&dir.join("missing.json")).unwrap_err();
+        assert!(err.to_string().contains("missing.json"), "{err}");
+    }
+
+    #[test]
+    fn archives() {
+        let dir = dir("archives");
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("data.json", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(DATA.as_bytes()).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        let files = [
+            ("data.zip", zip),
+            ("data.tar", tar()),
+            ("data.tar.gz", gzip(&tar())),
+            ("data.tgz", gzip(&tar())),
+            (
+                "data.tar.zst",
+                zstd::encode_all(tar().as_slice(), 0).unwrap(),
+            ),
+        ];
+        for (name, data) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, data).unwrap();
+            assert_eq!(read_archive(&path, "data.json").unwrap(), DATA, "{name}");
+
+            let err = read_archive(&path, "missing.json").unwrap_err();
+            assert!(
+                err.to_string().contains("does not contain"),
+                "{name}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_archive() {
+        let dir = dir("unknown");
+
+        let path = dir.join("data.rar");
+        std::fs::write(&path, tar()).unwrap();
+        let err = read_archive(&path, "data.json").unwrap_err();
+        assert!(
+            err.to_string().contains("is not a zip or tar archive"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn git_rev_option() {
+        let err = read_git(Path::new("."), "--output=/tmp/x", "data.json").unwrap_err();
+        assert!(err.to_string().contains("must not start with `-`"), "{err}");
+    }
+}
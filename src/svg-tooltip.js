@@ -0,0 +1,25 @@
+const root = document.getElementsByTagName('svg')[0];
+
+function stats(value) {
+    return value ? value.split(';;') : [];
+}
+
+// Shows the name, stats and changes of a node as its title, once it is hovered.
+root.addEventListener('pointerover', function(event) {
+    const node = event.target;
+    if (!node.dataset || node.dataset.name === undefined || node.querySelector('title')) {
+        return;
+    }
+
+    const lines = [node.dataset.name, ...stats(node.dataset.stats)];
+    if (node.dataset.change) {
+        lines.push('', `Changes: ${node.dataset.change.split(' ').join(', ')}`);
+    }
+    if (node.dataset.oldName !== undefined) {
+        lines.push('', `Previously: ${node.dataset.oldName}`, ...stats(node.dataset.oldStats));
+    }
+
+    const title = document.createElementNS('http://www.w3.org/2000/svg', 'title');
+    title.textContent = lines.join('\n');
+    node.appendChild(title);
+}, {passive: true});
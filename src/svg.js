@@ -6,6 +6,33 @@ const CONNECTIONS = [...root.querySelectorAll('.connections > *')]
 
 document.adoptedStyleSheets = [new CSSStyleSheet(), new CSSStyleSheet()];
 
+// The bloodline is either passed as `bloodlineId` or, like in tree urls,
+// shares the `alternateAscendancyId` with the alternate ascendancies.
+function ascendancy_rule(data) {
+    const bloodlineId = data.bloodlineId ?? data.alternateAscendancyId;
+    const names = [
+        window._ascendancy_name(data.classId, data.ascendancyId),
+        window._alternate_ascendancy_name(data.classId, data.alternateAscendancyId),
+        window._bloodline_name(data.classId, bloodlineId),
+    ].filter(name => name !== undefined);
+    if (names.length === 0) {
+        return null;
+    }
+
+    return `
+        ${names.map(name => `.${name}`).join(', ')}
+    { 
+        display: block !important;
+    }`;
+}
+
+function insert_ascendancy_rule(css, data) {
+    const rule = ascendancy_rule(data);
+    if (rule !== null) {
+        css.insertRule(rule);
+    }
+}
+
 window.tree_load = function(data) {
     const css = new CSSStyleSheet();
 
@@ -23,12 +50,37 @@ window.tree_load = function(data) {
     }
 
     // Activate ascendancy.
-    css.insertRule(`
-        .${window._ascendancy_name(data.classId, data.ascendancyId)}, 
-        .${window._alternate_ascendancy_name(data.classId, data.alternateAscendancyId)}
-    { 
-        display: block !important;
-    }`);
+    insert_ascendancy_rule(css, data);
+
+    document.adoptedStyleSheets[0] = css;
+}
+
+window.tree_compare = function(a, b) {
+    const css = new CSSStyleSheet();
+
+    const a_set = new Set(a.nodes);
+    const b_set = new Set(b.nodes);
+    const color = (in_a, in_b) => in_a && in_b
+        ? 'var(--both-color)'
+        : (in_a ? 'var(--only-a-color)' : 'var(--only-b-color)');
+
+    // Activate nodes of either build.
+    for (const node_id of new Set([...a.nodes, ...b.nodes])) {
+        css.insertRule(`#n${node_id} { color: ${color(a_set.has(node_id), b_set.has(node_id))} }`);
+    }
+
+    // Activate connections of either build.
+    for (const [x, y] of CONNECTIONS) {
+        const in_a = a_set.has(x) && a_set.has(y);
+        const in_b = b_set.has(x) && b_set.has(y);
+        if (in_a || in_b) {
+            css.insertRule(`#c${x}-${y} { color: ${color(in_a, in_b)} }`);
+        }
+    }
+
+    // Activate ascendancies of both builds.
+    insert_ascendancy_rule(css, a);
+    insert_ascendancy_rule(css, b);
 
     document.adoptedStyleSheets[0] = css;
 }
@@ -1,7 +1,15 @@
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
 use std::io::Write;
 
-use crate::tree::{AscendancyNodeKind, NodeKind, Path, Sweep, Tree};
+use crate::allocation::Allocation;
+use crate::data;
+use crate::diff::{self, Change, Diff, NodeDiff};
+use crate::heatmap::{self, Heatmap};
+use crate::tree::{
+    self, Ascendancy, AscendancyNodeKind, Connection, Coord, Node, NodeKind, Path, Sweep, Tree,
+};
 
 const STYLES: &str = r#"
 svg {
@@ -9,6 +17,9 @@ svg {
     --active-color: #38bdf8;
     --default-color: #64748b;
     --highlight-color: #facc15;
+    --only-a-color: #f472b6;
+    --only-b-color: #a78bfa;
+    --both-color: #38bdf8;
 
     background-color: var(--bg-color);
     color: var(--default-color);
@@ -39,10 +50,125 @@ svg {
 "#;
 const SCRIPT: &str = include_str!("svg.js");
 const SCRIPT_MOUSE: &str = include_str!("svg-mouse.js");
+const SCRIPT_TOOLTIP: &str = include_str!("svg-tooltip.js");
 
 const OFFSET: u32 = 100;
 
-pub fn render(tree: &Tree, output: &mut dyn Write) -> anyhow::Result<()> {
+const BG_COLOR: &str = "#1e293b";
+const ACTIVE_COLOR: &str = "#38bdf8";
+const DEFAULT_COLOR: &str = "#64748b";
+const HIGHLIGHT_COLOR: &str = "#facc15";
+const LABEL_COLOR: &str = "#f8fafc";
+const ADDED_COLOR: &str = "#4ade80";
+const REMOVED_COLOR: &str = "#f87171";
+const CHANGED_COLOR: &str = "#fbbf24";
+const ONLY_A_COLOR: &str = "#f472b6";
+const ONLY_B_COLOR: &str = "#a78bfa";
+const BOTH_COLOR: &str = "#38bdf8";
+/// Strokes of the characters of labels, see [`write_label`].
+///
+/// Characters are 4 units wide and at most 6 units high, with the baseline at 0.
+/// resvg is built without text support, labels are drawn as paths to show up in PNGs.
+/// Only digits, spaces and the letters of the heatmap legend are supported.
+const GLYPHS: &[(char, Glyph)] = &[
+    ('0', &[&[(0, -6), (4, -6), (4, 0), (0, 0), (0, -6)]]),
+    ('1', &[&[(1, -5), (2, -6), (2, 0)], &[(1, 0), (3, 0)]]),
+    (
+        '2',
+        &[&[(0, -6), (4, -6), (4, -3), (0, -3), (0, 0), (4, 0)]],
+    ),
+    (
+        '3',
+        &[&[(0, -6), (4, -6), (4, 0), (0, 0)], &[(1, -3), (4, -3)]],
+    ),
+    ('4', &[&[(0, -6), (0, -3), (4, -3)], &[(4, -6), (4, 0)]]),
+    (
+        '5',
+        &[&[(4, -6), (0, -6), (0, -3), (4, -3), (4, 0), (0, 0)]],
+    ),
+    (
+        '6',
+        &[&[(4, -6), (0, -6), (0, 0), (4, 0), (4, -3), (0, -3)]],
+    ),
+    ('7', &[&[(0, -6), (4, -6), (4, 0)]]),
+    (
+        '8',
+        &[
+            &[(0, -6), (4, -6), (4, 0), (0, 0), (0, -6)],
+            &[(0, -3), (4, -3)],
+        ],
+    ),
+    (
+        '9',
+        &[&[(4, -3), (0, -3), (0, -6), (4, -6), (4, 0), (0, 0)]],
+    ),
+    ('b', &[&[(0, -6), (0, 0), (4, 0), (4, -4), (0, -4)]]),
+    ('d', &[&[(4, -6), (4, 0), (0, 0), (0, -4), (4, -4)]]),
+    ('f', &[&[(3, -6), (1, -6), (1, 0)], &[(0, -4), (3, -4)]]),
+    ('i', &[&[(2, -4), (2, 0)], &[(2, -6), (2, -5)]]),
+    ('l', &[&[(2, -6), (2, 0)]]),
+    ('o', &[&[(0, -4), (4, -4), (4, 0), (0, 0), (0, -4)]]),
+    (
+        's',
+        &[&[(4, -4), (0, -4), (0, -2), (4, -2), (4, 0), (0, 0)]],
+    ),
+    ('u', &[&[(0, -4), (0, 0), (4, 0), (4, -4)]]),
+];
+/// Strokes of a character, each stroke is a line through its points.
+type Glyph = &'static [&'static [(i32, i32)]];
+/// Horizontal distance between characters of labels, in units of [`GLYPHS`].
+const GLYPH_ADVANCE: i32 = 6;
+
+/// Color stops of heatmaps, from rarely to most allocated.
+const HEAT_COLORS: [(u8, u8, u8); 3] = [(29, 78, 216), (250, 204, 21), (239, 68, 68)];
+
+/// A build to bake into a static render, see [`render_build`].
+#[derive(Debug, Default, Clone)]
+pub struct Build {
+    pub nodes: BTreeSet<u32>,
+    pub ascendancy: Option<Ascendancy>,
+    pub alternate_ascendancy: Option<Ascendancy>,
+    /// Allocated nodes to highlight, e.g. the nodes of a [`crate::plan::Plan`].
+    pub highlighted: BTreeSet<u32>,
+}
+
+impl Build {
+    /// Resolves the class and ascendancy ids of `allocation` against `tree`.
+    pub fn from_allocation(tree: &Tree, allocation: &Allocation) -> Self {
+        Self {
+            nodes: allocation
+                .nodes
+                .iter()
+                .map(|&node| u32::from(node))
+                .chain(allocation.cluster_nodes.iter().copied())
+                .collect(),
+            ascendancy: tree.ascendancy(allocation.class, allocation.ascendancy),
+            alternate_ascendancy: tree
+                .alternate_ascendancy(allocation.class, allocation.alternate_ascendancy),
+            highlighted: BTreeSet::new(),
+        }
+    }
+
+    fn is_active(&self, id: u32) -> bool {
+        self.nodes.contains(&id)
+    }
+
+    fn is_visible(&self, kind: &NodeKind) -> bool {
+        match kind {
+            NodeKind::Ascendancy { ascendancy, .. } => {
+                self.ascendancy.as_ref() == Some(ascendancy)
+                    || self.alternate_ascendancy.as_ref() == Some(ascendancy)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Renders the SVG template of `tree`.
+///
+/// The template contains every node and ascendancy, the embedded script
+/// activates the nodes of a build at runtime.
+pub fn render(tree: &Tree, output: &mut dyn Write) -> crate::Result<()> {
     macro_rules! w {
         ($($tts:tt)*) => {
             writeln!(output, $($tts)*)?
@@ -61,60 +187,26 @@ pub fn render(tree: &Tree, output: &mut dyn Write) -> anyhow::Result<()> {
 
     w!(r#"<g class="connections" fill="none" stroke-width="20" stroke="currentColor">"#);
     for connection in &tree.connections {
-        let x1 = connection.a.position.x;
-        let y1 = connection.a.position.y;
-        let x2 = connection.b.position.x;
-        let y2 = connection.b.position.y;
-
-        let a = connection.a.id.min(connection.b.id);
-        let b = connection.a.id.max(connection.b.id);
-
-        let class = match connection.a.kind {
+        let class = match &connection.a.kind {
             NodeKind::Ascendancy { ascendancy, .. } => {
                 format!(r#"class="ascendancy {}""#, ascendancy.as_ref())
             }
             _ => String::new(),
         };
 
-        match &connection.path {
-            Path::Arc { sweep, radius: r } => {
-                let sweep = match sweep {
-                    Sweep::Clockwise => 1,
-                    Sweep::CounterClockwise => 0,
-                };
-                w!(
-                    r#"<path d="M {x1} {y1} A {r} {r} 0 0 {sweep} {x2} {y2}" id="c{a}-{b}" {class} />"#
-                );
-            }
-            Path::Line {} => {
-                w!(r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" id="c{a}-{b}" {class} />"#);
-            }
-        }
+        write_connection(output, connection, &class)?;
     }
     w!("</g>");
 
     w!(r#"<g class="nodes" stroke="currentColor" fill="currentColor">"#);
     for node in &tree.nodes {
-        let attrs: Cow<'static, str> = match node.kind {
-            NodeKind::Mastery => r#"r="50" class="mastery""#.into(),
-            NodeKind::Keystone => r#"r="80" class="keystone""#.into(),
-            NodeKind::Ascendancy { kind, ascendancy } => {
-                let name = ascendancy.as_ref();
-                use AscendancyNodeKind::*;
-                match kind {
-                    Start => "".into(),
-                    Notable => format!(r#"r="65" class="ascendancy {name}""#).into(),
-                    Normal => format!(r#"r="45" class="ascendancy {name}""#).into(),
-                }
-            }
-            _ => r#"r="50""#.into(),
-        };
         w!(
-            r#"<circle cx="{}" cy="{}" id="n{}" {attrs} data-name="{}" data-stats="{}" data-kind="{}" />"#,
+            r#"<circle cx="{}" cy="{}" id="n{}" {} data-name="{}" data-stats="{}" data-kind="{}" />"#,
             node.position.x,
             node.position.y,
             node.id,
-            node.meta.name,
+            node_attrs(node),
+            escape(&node.meta.name),
             encode(&node.meta.stats),
             node.kind.as_str(),
         );
@@ -135,6 +227,22 @@ pub fn render(tree: &Tree, output: &mut dyn Write) -> anyhow::Result<()> {
     w!(r#"}}"#);
     w!(r#"window._alternate_ascendancy_name = function(classId, ascendancyId) {{"#);
     for (name, info) in tree.alternate_ascendancies.iter() {
+        if tree.bloodlines.contains(name) {
+            continue;
+        }
+        w!(
+            r#"if (classId === {} && ascendancyId === {}) {{ return "{}" }}"#,
+            info.class,
+            info.ascendancy,
+            name.as_ref()
+        );
+    }
+    w!(r#"}}"#);
+    w!(r#"window._bloodline_name = function(classId, ascendancyId) {{"#);
+    for (name, info) in tree.alternate_ascendancies.iter() {
+        if !tree.bloodlines.contains(name) {
+            continue;
+        }
         w!(
             r#"if (classId === {} && ascendancyId === {}) {{ return "{}" }}"#,
             info.class,
@@ -153,9 +261,1103 @@ pub fn render(tree: &Tree, output: &mut dyn Write) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Renders a static SVG of `tree` with `build` allocated.
+///
+/// Unlike [`render`] the output does not require any scripts or stylesheets,
+/// colors are baked into the elements and only the selected ascendancies are included.
+/// Highlighted nodes and their connections use the highlight color.
+pub fn render_build(tree: &Tree, build: &Build, output: &mut dyn Write) -> crate::Result<()> {
+    macro_rules! w {
+        ($($tts:tt)*) => {
+            writeln!(output, $($tts)*)?
+        }
+    }
+
+    let x = tree.view_box.x - OFFSET as i32;
+    let y = tree.view_box.y - OFFSET as i32;
+    let dx = tree.view_box.dx + OFFSET * 2;
+    let dy = tree.view_box.dy + OFFSET * 2;
+
+    w!(r#"<svg viewBox="{x} {y} {dx} {dy}" xmlns="http://www.w3.org/2000/svg">"#);
+    w!(r#"<rect x="{x}" y="{y}" width="{dx}" height="{dy}" fill="{BG_COLOR}" />"#);
+
+    w!(r#"<g class="connections" fill="none" stroke-width="20" stroke="{DEFAULT_COLOR}">"#);
+    for connection in &tree.connections {
+        if !build.is_visible(&connection.a.kind) {
+            continue;
+        }
+
+        let is_active = build.is_active(connection.a.id) && build.is_active(connection.b.id);
+        let is_highlighted = build.highlighted.contains(&connection.a.id)
+            || build.highlighted.contains(&connection.b.id);
+        let stroke = match (is_active, is_highlighted) {
+            (true, true) => format!(r#"stroke="{HIGHLIGHT_COLOR}""#),
+            (true, false) => format!(r#"stroke="{ACTIVE_COLOR}""#),
+            (false, _) => String::new(),
+        };
+
+        write_connection(output, connection, &stroke)?;
+    }
+    w!("</g>");
+
+    w!(r#"<g class="nodes" fill="{DEFAULT_COLOR}">"#);
+    for node in &tree.nodes {
+        if !build.is_visible(&node.kind) {
+            continue;
+        }
+
+        let fill = match (build.is_active(node.id), &node.kind) {
+            (true, _) if build.highlighted.contains(&node.id) => HIGHLIGHT_COLOR,
+            (true, _) => ACTIVE_COLOR,
+            (false, NodeKind::Mastery) => continue,
+            (false, _) => DEFAULT_COLOR,
+        };
+
+        w!(
+            r#"<circle cx="{}" cy="{}" id="n{}" {} fill="{fill}" />"#,
+            node.position.x,
+            node.position.y,
+            node.id,
+            node_attrs(node),
+        );
+    }
+    w!("</g>");
+
+    w!("</svg>");
+
+    Ok(())
+}
+
+/// Allocation order of a build, see [`render_timeline`].
+#[derive(Debug, Default, Clone)]
+pub struct Timeline {
+    /// The build after all steps.
+    pub build: Build,
+    /// Nodes allocated in each step, in order.
+    pub steps: Vec<Vec<u32>>,
+}
+
+impl Timeline {
+    /// Creates a timeline which allocates one node per step.
+    ///
+    /// The ascendancy is the ascendancy of the first ascendancy node.
+    pub fn from_nodes(tree: &Tree, nodes: impl IntoIterator<Item = u32>) -> Self {
+        let mut timeline = Self::default();
+
+        for id in nodes {
+            if !timeline.build.nodes.insert(id) {
+                continue;
+            }
+            timeline.steps.push(vec![id]);
+
+            let ascendancy = tree.nodes.iter().find_map(|node| match &node.kind {
+                NodeKind::Ascendancy { ascendancy, .. } if node.id == id => {
+                    Some(ascendancy.clone())
+                }
+                _ => None,
+            });
+            if let Some(ascendancy) = ascendancy {
+                if tree.ascendancies.contains_key(&ascendancy) {
+                    timeline.build.ascendancy.get_or_insert(ascendancy);
+                } else {
+                    timeline
+                        .build
+                        .alternate_ascendancy
+                        .get_or_insert(ascendancy);
+                }
+            }
+        }
+
+        timeline
+    }
+
+    /// Creates a timeline from a sequence of builds, e.g. a build at different levels.
+    ///
+    /// Every build is a step with the nodes which are not allocated by a previous
+    /// build, the ascendancies are taken from the last build.
+    pub fn from_builds(builds: impl IntoIterator<Item = Build>) -> Self {
+        let mut timeline = Self::default();
+
+        for build in builds {
+            let step = build
+                .nodes
+                .iter()
+                .copied()
+                .filter(|id| !timeline.build.nodes.contains(id))
+                .collect();
+            timeline.steps.push(step);
+
+            timeline.build.nodes.extend(build.nodes);
+            timeline.build.ascendancy = build.ascendancy;
+            timeline.build.alternate_ascendancy = build.alternate_ascendancy;
+        }
+
+        timeline
+    }
+}
+
+/// How [`render_timeline`] shows the allocation order.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TimelineStyle {
+    /// Every allocated node is labeled with its step number.
+    Numbered,
+    /// Allocated nodes and connections are revealed step by step with CSS animations.
+    Animated {
+        /// Duration of a single step in seconds.
+        step_duration: f32,
+    },
+}
+
+/// Renders a static SVG of `tree` with the allocation order of `timeline`.
+///
+/// Like [`render_build`] the colors are baked into the elements, animations
+/// refer to the `n{id}` and `c{a}-{b}` ids of nodes and connections.
+pub fn render_timeline(
+    tree: &Tree,
+    timeline: &Timeline,
+    style: TimelineStyle,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    macro_rules! w {
+        ($($tts:tt)*) => {
+            writeln!(output, $($tts)*)?
+        }
+    }
+
+    let build = &timeline.build;
+    let steps: BTreeMap<_, _> = timeline
+        .steps
+        .iter()
+        .enumerate()
+        .flat_map(|(step, nodes)| nodes.iter().map(move |&id| (id, step)))
+        .collect();
+
+    let x = tree.view_box.x - OFFSET as i32;
+    let y = tree.view_box.y - OFFSET as i32;
+    let dx = tree.view_box.dx + OFFSET * 2;
+    let dy = tree.view_box.dy + OFFSET * 2;
+
+    w!(r#"<svg viewBox="{x} {y} {dx} {dy}" xmlns="http://www.w3.org/2000/svg">"#);
+
+    if let TimelineStyle::Animated { step_duration } = style {
+        let mut rules = BTreeMap::<_, Vec<_>>::new();
+        for node in &tree.nodes {
+            if let Some(&step) = steps.get(&node.id) {
+                rules
+                    .entry(step)
+                    .or_default()
+                    .push(format!("#n{}", node.id));
+            }
+        }
+        let mut connection_rules = BTreeMap::<_, Vec<_>>::new();
+        for connection in &tree.connections {
+            if let (Some(&a), Some(&b)) = (steps.get(&connection.a.id), steps.get(&connection.b.id))
+            {
+                let (a_id, b_id) = ordered(connection.a.id, connection.b.id);
+                connection_rules
+                    .entry(a.max(b))
+                    .or_default()
+                    .push(format!("#c{a_id}-{b_id}"));
+            }
+        }
+
+        w!("<style>");
+        w!("@keyframes reveal-node {{ to {{ fill: {ACTIVE_COLOR} }} }}");
+        w!("@keyframes reveal-connection {{ to {{ stroke: {ACTIVE_COLOR} }} }}");
+        for (step, selectors) in rules {
+            let delay = step as f32 * step_duration;
+            w!(
+                "{} {{ animation: reveal-node {step_duration}s {delay}s forwards }}",
+                selectors.join(", ")
+            );
+        }
+        for (step, selectors) in connection_rules {
+            let delay = step as f32 * step_duration;
+            w!(
+                "{} {{ animation: reveal-connection {step_duration}s {delay}s forwards }}",
+                selectors.join(", ")
+            );
+        }
+        w!("</style>");
+    }
+
+    // Animated renders start out unallocated.
+    let active = match style {
+        TimelineStyle::Numbered => ACTIVE_COLOR,
+        TimelineStyle::Animated { .. } => DEFAULT_COLOR,
+    };
+
+    w!(r#"<rect x="{x}" y="{y}" width="{dx}" height="{dy}" fill="{BG_COLOR}" />"#);
+
+    w!(r#"<g class="connections" fill="none" stroke-width="20" stroke="{DEFAULT_COLOR}">"#);
+    for connection in &tree.connections {
+        if !build.is_visible(&connection.a.kind) {
+            continue;
+        }
+
+        let stroke = if build.is_active(connection.a.id) && build.is_active(connection.b.id) {
+            format!(r#"stroke="{active}""#)
+        } else {
+            String::new()
+        };
+
+        write_connection(output, connection, &stroke)?;
+    }
+    w!("</g>");
+
+    w!(r#"<g class="nodes" fill="{DEFAULT_COLOR}">"#);
+    for node in &tree.nodes {
+        if !build.is_visible(&node.kind) {
+            continue;
+        }
+
+        let fill = match (build.is_active(node.id), &node.kind) {
+            (true, _) => active,
+            (false, NodeKind::Mastery) => continue,
+            (false, _) => DEFAULT_COLOR,
+        };
+
+        w!(
+            r#"<circle cx="{}" cy="{}" id="n{}" {} fill="{fill}" />"#,
+            node.position.x,
+            node.position.y,
+            node.id,
+            node_attrs(node),
+        );
+    }
+    w!("</g>");
+
+    if style == TimelineStyle::Numbered {
+        w!(r#"<g class="steps" fill="none" stroke="{LABEL_COLOR}" stroke-linecap="round">"#);
+        for node in tree
+            .nodes
+            .iter()
+            .filter(|node| build.is_visible(&node.kind))
+        {
+            if let Some(step) = steps.get(&node.id) {
+                let label = (step + 1).to_string();
+                write_label(output, node.position, 42, Anchor::Center, &label)?;
+            }
+        }
+        w!("</g>");
+    }
+
+    w!("</svg>");
+
+    Ok(())
+}
+
+/// Renders a static SVG of `tree` with two builds `a` and `b` allocated.
+///
+/// Nodes and connections are colored by whether they are allocated only in `a`,
+/// only in `b` or in both builds, the ascendancies of both builds are included.
+pub fn render_comparison(
+    tree: &Tree,
+    a: &Build,
+    b: &Build,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    macro_rules! w {
+        ($($tts:tt)*) => {
+            writeln!(output, $($tts)*)?
+        }
+    }
+
+    let color = |in_a: bool, in_b: bool| match (in_a, in_b) {
+        (true, true) => Some(BOTH_COLOR),
+        (true, false) => Some(ONLY_A_COLOR),
+        (false, true) => Some(ONLY_B_COLOR),
+        (false, false) => None,
+    };
+    let is_visible = |kind: &NodeKind| a.is_visible(kind) || b.is_visible(kind);
+
+    let x = tree.view_box.x - OFFSET as i32;
+    let y = tree.view_box.y - OFFSET as i32;
+    let dx = tree.view_box.dx + OFFSET * 2;
+    let dy = tree.view_box.dy + OFFSET * 2;
+
+    w!(r#"<svg viewBox="{x} {y} {dx} {dy}" xmlns="http://www.w3.org/2000/svg">"#);
+    w!(r#"<rect x="{x}" y="{y}" width="{dx}" height="{dy}" fill="{BG_COLOR}" />"#);
+
+    w!(r#"<g class="connections" fill="none" stroke-width="20" stroke="{DEFAULT_COLOR}">"#);
+    for connection in &tree.connections {
+        if !is_visible(&connection.a.kind) {
+            continue;
+        }
+
+        let in_a = a.is_active(connection.a.id) && a.is_active(connection.b.id);
+        let in_b = b.is_active(connection.a.id) && b.is_active(connection.b.id);
+        let stroke = match color(in_a, in_b) {
+            Some(color) => format!(r#"stroke="{color}""#),
+            None => String::new(),
+        };
+
+        write_connection(output, connection, &stroke)?;
+    }
+    w!("</g>");
+
+    w!(r#"<g class="nodes" fill="{DEFAULT_COLOR}">"#);
+    for node in &tree.nodes {
+        if !is_visible(&node.kind) {
+            continue;
+        }
+
+        let fill = match (
+            color(a.is_active(node.id), b.is_active(node.id)),
+            &node.kind,
+        ) {
+            (Some(color), _) => color,
+            (None, NodeKind::Mastery) => continue,
+            (None, _) => DEFAULT_COLOR,
+        };
+
+        w!(
+            r#"<circle cx="{}" cy="{}" id="n{}" {} fill="{fill}" />"#,
+            node.position.x,
+            node.position.y,
+            node.id,
+            node_attrs(node),
+        );
+    }
+    w!("</g>");
+
+    w!("</svg>");
+
+    Ok(())
+}
+
+/// Renders an SVG of the changes between the `old` and `new` tree, see [`diff::diff`].
+///
+/// Both trees are laid out with [`diff::options`], which never places ascendancies
+/// on top of each other.
+/// Added nodes and connections are green, removed ones red and changed ones amber.
+/// Changed nodes carry their previous name and stats in `data-old-name` and
+/// `data-old-stats`, next to the `data-name` and `data-stats` of the template,
+/// a script shows them as a tooltip on hover.
+pub fn render_diff(
+    old: &data::Tree,
+    new: &data::Tree,
+    diff: &Diff,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    macro_rules! w {
+        ($($tts:tt)*) => {
+            writeln!(output, $($tts)*)?
+        }
+    }
+
+    let old = &tree::build(old, &diff::options())?;
+    let new = &tree::build(new, &diff::options())?;
+
+    let view_box = old.view_box.union(&new.view_box);
+    let x = view_box.x - OFFSET as i32;
+    let y = view_box.y - OFFSET as i32;
+    let dx = view_box.dx + OFFSET * 2;
+    let dy = view_box.dy + OFFSET * 2;
+
+    w!(r#"<svg viewBox="{x} {y} {dx} {dy}" xmlns="http://www.w3.org/2000/svg">"#);
+    w!(
+        r#"<style>.nodes circle:hover {{ stroke: {HIGHLIGHT_COLOR}; stroke-width: 30px; }}</style>"#
+    );
+    w!(r#"<rect x="{x}" y="{y}" width="{dx}" height="{dy}" fill="{BG_COLOR}" />"#);
+
+    // Connections of nodes which changed their id are matched by the new id.
+    let ids: BTreeMap<_, _> = diff
+        .nodes
+        .iter()
+        .filter_map(|node| Some((node.old.as_ref()?.id, node.new.as_ref()?.id)))
+        .collect();
+    let new_id = |id| ids.get(&id).copied().unwrap_or(id);
+
+    let old_connections: BTreeMap<_, _> = old
+        .connections
+        .iter()
+        .map(|connection| {
+            let key = ordered(new_id(connection.a.id), new_id(connection.b.id));
+            (key, connection)
+        })
+        .collect();
+    let new_connections: BTreeSet<_> = new
+        .connections
+        .iter()
+        .map(|connection| ordered(connection.a.id, connection.b.id))
+        .collect();
+
+    w!(r#"<g class="connections" fill="none" stroke-width="20" stroke="{DEFAULT_COLOR}">"#);
+    for connection in &new.connections {
+        let positions =
+            |connection: &Connection| ordered(connection.a.position, connection.b.position);
+
+        let stroke = match old_connections.get(&ordered(connection.a.id, connection.b.id)) {
+            None => format!(r#"stroke="{ADDED_COLOR}""#),
+            Some(old) if positions(old) != positions(connection) => {
+                format!(r#"stroke="{CHANGED_COLOR}""#)
+            }
+            Some(_) => String::new(),
+        };
+
+        write_connection(output, connection, &stroke)?;
+    }
+    for (_, connection) in old_connections
+        .iter()
+        .filter(|(key, _)| !new_connections.contains(key))
+    {
+        write_connection(output, connection, &format!(r#"stroke="{REMOVED_COLOR}""#))?;
+    }
+    w!("</g>");
+
+    let changes: BTreeMap<_, _> = diff
+        .nodes
+        .iter()
+        .filter_map(|node| Some((node.new.as_ref()?.id, node)))
+        .collect();
+    let removed: BTreeSet<_> = diff
+        .nodes
+        .iter()
+        .filter(|node| node.new.is_none())
+        .filter_map(|node| Some(node.old.as_ref()?.id))
+        .collect();
+
+    w!(r#"<g class="nodes" fill="{DEFAULT_COLOR}">"#);
+    for node in &new.nodes {
+        let change = changes.get(&node.id).copied();
+        let fill = match (change, &node.kind) {
+            (Some(change), _) if change.changes.contains(&Change::Added) => ADDED_COLOR,
+            (Some(_), _) => CHANGED_COLOR,
+            (None, NodeKind::Mastery) => continue,
+            (None, _) => DEFAULT_COLOR,
+        };
+
+        write_diff_node(output, node, fill, change)?;
+    }
+    for node in old.nodes.iter().filter(|node| removed.contains(&node.id)) {
+        write_diff_node(output, node, REMOVED_COLOR, None)?;
+    }
+    w!("</g>");
+
+    w!(r#"<script><![CDATA[(function() {{ {SCRIPT_TOOLTIP} }})()]]></script>"#);
+    w!("</svg>");
+
+    Ok(())
+}
+
+/// Renders a static SVG of the tree `data` with the allocation counts of `heatmap`.
+///
+/// The tree is laid out with [`heatmap::options`], ascendancies of many builds are
+/// shown at once and must not be placed on top of each other.
+/// Allocated nodes and connections are colored by how often they are allocated
+/// relative to the most allocated node, connections are also drawn wider.
+/// Only ascendancies with allocated nodes are included, a legend below the tree
+/// maps the colors to allocation counts.
+pub fn render_heatmap(
+    data: &data::Tree,
+    heatmap: &Heatmap,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    macro_rules! w {
+        ($($tts:tt)*) => {
+            writeln!(output, $($tts)*)?
+        }
+    }
+
+    let tree = &tree::build(data, &heatmap::options())?;
+
+    let max = heatmap.max().max(1) as f32;
+    let heat = |count: u32| count as f32 / max;
+
+    let ascendancies: BTreeSet<_> = tree
+        .nodes
+        .iter()
+        .filter(|node| heatmap.nodes.contains_key(&node.id))
+        .filter_map(|node| match &node.kind {
+            NodeKind::Ascendancy { ascendancy, .. } => Some(ascendancy.clone()),
+            _ => None,
+        })
+        .collect();
+    let is_visible = |kind: &NodeKind| match kind {
+        NodeKind::Ascendancy { ascendancy, .. } => ascendancies.contains(ascendancy),
+        _ => true,
+    };
+
+    let x = tree.view_box.x - OFFSET as i32;
+    let y = tree.view_box.y - OFFSET as i32;
+    let dx = tree.view_box.dx + OFFSET * 2;
+    // Room for the legend below the tree.
+    let legend = dx / 20;
+    let dy = tree.view_box.dy + OFFSET * 2 + legend;
+
+    w!(r#"<svg viewBox="{x} {y} {dx} {dy}" xmlns="http://www.w3.org/2000/svg">"#);
+    w!("<defs>");
+    w!(r#"<linearGradient id="heat">"#);
+    for (i, &color) in HEAT_COLORS.iter().enumerate() {
+        let offset = i as f32 / (HEAT_COLORS.len() - 1) as f32;
+        w!(r#"<stop offset="{offset}" stop-color="{}" />"#, rgb(color));
+    }
+    w!("</linearGradient>");
+    w!("</defs>");
+    w!(r#"<rect x="{x}" y="{y}" width="{dx}" height="{dy}" fill="{BG_COLOR}" />"#);
+
+    w!(r#"<g class="connections" fill="none" stroke-width="20" stroke="{DEFAULT_COLOR}">"#);
+    for connection in &tree.connections {
+        if !is_visible(&connection.a.kind) {
+            continue;
+        }
+
+        let key = ordered(connection.a.id, connection.b.id);
+        let stroke = match heatmap.connections.get(&key) {
+            Some(&count) => {
+                let t = heat(count);
+                format!(
+                    r#"stroke="{}" stroke-width="{}" data-count="{count}""#,
+                    heat_color(t),
+                    (20.0 + 40.0 * t).round()
+                )
+            }
+            None => String::new(),
+        };
+
+        write_connection(output, connection, &stroke)?;
+    }
+    w!("</g>");
+
+    w!(r#"<g class="nodes" fill="{DEFAULT_COLOR}">"#);
+    for node in &tree.nodes {
+        if !is_visible(&node.kind) {
+            continue;
+        }
+
+        let (fill, count) = match (heatmap.nodes.get(&node.id), &node.kind) {
+            (Some(&count), _) => (heat_color(heat(count)), count),
+            (None, NodeKind::Mastery) => continue,
+            (None, _) => (DEFAULT_COLOR.to_owned(), 0),
+        };
+
+        w!(
+            r#"<circle cx="{}" cy="{}" id="n{}" {} fill="{fill}" data-count="{count}" />"#,
+            node.position.x,
+            node.position.y,
+            node.id,
+            node_attrs(node),
+        );
+    }
+    w!("</g>");
+
+    let legend_x = x + OFFSET as i32;
+    let legend_y = y + (dy - legend) as i32;
+    let width = dx / 4;
+    let height = legend / 4;
+    let font_size = legend / 5;
+    let text_y = legend_y + (height * 3 / 2 + font_size) as i32;
+    w!(r#"<g class="legend" fill="none" stroke="{LABEL_COLOR}" stroke-linecap="round">"#);
+    w!(
+        r#"<rect x="{legend_x}" y="{legend_y}" width="{width}" height="{height}" fill="url(#heat)" stroke="none" />"#
+    );
+    let start = Coord {
+        x: legend_x,
+        y: text_y,
+    };
+    write_label(output, start, font_size, Anchor::Start, "0")?;
+    let end = Coord {
+        x: legend_x + width as i32,
+        y: text_y,
+    };
+    let label = format!("{} of {} builds", heatmap.max(), heatmap.builds);
+    write_label(output, end, font_size, Anchor::End, &label)?;
+    w!("</g>");
+
+    w!("</svg>");
+
+    Ok(())
+}
+
+/// Where a label is placed relative to its position, see [`write_label`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Anchor {
+    /// The position is the left end of the baseline.
+    Start,
+    /// The position is the right end of the baseline.
+    End,
+    /// The position is the center of the label.
+    Center,
+}
+
+/// Writes `text` as a path of strokes `size` high, see [`GLYPHS`].
+///
+/// The stroke color is inherited, the original text is kept as the title of the path.
+fn write_label(
+    output: &mut dyn Write,
+    position: Coord,
+    size: u32,
+    anchor: Anchor,
+    text: &str,
+) -> crate::Result<()> {
+    let scale = size as f32 / 6.0;
+    let width = (text.chars().count() as i32 * GLYPH_ADVANCE - (GLYPH_ADVANCE - 4)) as f32 * scale;
+    let (x, y) = match anchor {
+        Anchor::Start => (position.x as f32, position.y as f32),
+        Anchor::End => (position.x as f32 - width, position.y as f32),
+        Anchor::Center => (
+            position.x as f32 - width / 2.0,
+            position.y as f32 + 3.0 * scale,
+        ),
+    };
+
+    let mut path = String::new();
+    for (i, c) in text.chars().enumerate() {
+        let Some((_, strokes)) = GLYPHS.iter().find(|(glyph, _)| *glyph == c) else {
+            continue;
+        };
+        let offset = i as i32 * GLYPH_ADVANCE;
+        for stroke in *strokes {
+            for (j, &(dx, dy)) in stroke.iter().enumerate() {
+                let command = if j == 0 { 'M' } else { 'L' };
+                let _ = write!(
+                    path,
+                    "{command} {} {} ",
+                    (x + (offset + dx) as f32 * scale).round(),
+                    (y + dy as f32 * scale).round()
+                );
+            }
+        }
+    }
+
+    writeln!(
+        output,
+        r#"<path class="label" d="{}" stroke-width="{}"><title>{text}</title></path>"#,
+        path.trim_end(),
+        (scale * 0.8).round().max(1.0)
+    )?;
+    Ok(())
+}
+
+fn write_diff_node(
+    output: &mut dyn Write,
+    node: &Node,
+    fill: &str,
+    change: Option<&NodeDiff>,
+) -> crate::Result<()> {
+    let mut attrs = String::new();
+    if let Some(change) = change {
+        let changes: Vec<_> = change.changes.iter().map(Change::as_str).collect();
+        attrs.push_str(&format!(r#" data-change="{}""#, changes.join(" ")));
+
+        if let Some(old) = &change.old {
+            attrs.push_str(&format!(
+                r#" data-old-name="{}" data-old-stats="{}""#,
+                escape(&old.name),
+                encode(&old.stats)
+            ));
+        }
+    }
+
+    writeln!(
+        output,
+        r#"<circle cx="{}" cy="{}" id="n{}" {} fill="{fill}" data-name="{}" data-stats="{}" data-kind="{}"{attrs} />"#,
+        node.position.x,
+        node.position.y,
+        node.id,
+        node_attrs(node),
+        escape(&node.meta.name),
+        encode(&node.meta.stats),
+        node.kind.as_str(),
+    )?;
+
+    Ok(())
+}
+
+fn write_connection(
+    output: &mut dyn Write,
+    connection: &Connection,
+    attrs: &str,
+) -> crate::Result<()> {
+    let x1 = connection.a.position.x;
+    let y1 = connection.a.position.y;
+    let x2 = connection.b.position.x;
+    let y2 = connection.b.position.y;
+
+    let a = connection.a.id.min(connection.b.id);
+    let b = connection.a.id.max(connection.b.id);
+
+    match &connection.path {
+        Path::Arc { sweep, radius: r } => {
+            let sweep = match sweep {
+                Sweep::Clockwise => 1,
+                Sweep::CounterClockwise => 0,
+            };
+            writeln!(
+                output,
+                r#"<path d="M {x1} {y1} A {r} {r} 0 0 {sweep} {x2} {y2}" id="c{a}-{b}" {attrs} />"#
+            )?;
+        }
+        Path::Line {} => {
+            writeln!(
+                output,
+                r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" id="c{a}-{b}" {attrs} />"#
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Color of a heatmap value between `0.0` and `1.0`, interpolated between the [`HEAT_COLORS`].
+fn heat_color(t: f32) -> String {
+    let t = t.clamp(0.0, 1.0) * (HEAT_COLORS.len() - 1) as f32;
+    let i = (t.floor() as usize).min(HEAT_COLORS.len() - 2);
+    let (a, b) = (HEAT_COLORS[i], HEAT_COLORS[i + 1]);
+    let t = t - i as f32;
+
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    rgb((lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2)))
+}
+
+fn rgb((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn ordered<T: Ord>(a: T, b: T) -> (T, T) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn node_attrs(node: &Node) -> Cow<'static, str> {
+    match &node.kind {
+        NodeKind::Mastery => r#"r="50" class="mastery""#.into(),
+        NodeKind::Keystone => r#"r="80" class="keystone""#.into(),
+        NodeKind::Ascendancy { kind, ascendancy } => {
+            let name = ascendancy.as_ref();
+            use AscendancyNodeKind::*;
+            match kind {
+                Start => "".into(),
+                Notable => format!(r#"r="65" class="ascendancy {name}""#).into(),
+                Normal => format!(r#"r="45" class="ascendancy {name}""#).into(),
+            }
+        }
+        _ => r#"r="50""#.into(),
+    }
+}
+
+/// Escapes `s` for use in an attribute value.
+fn escape(s: &str) -> Cow<'_, str> {
+    if !s.contains(['&', '<', '>', '"']) {
+        return s.into();
+    }
+
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .into()
+}
+
 fn encode(s: &[String]) -> String {
     s.iter()
-        .map(|s| s.replace('\n', "&#010;"))
+        .map(|s| escape(s).replace('\n', "&#010;"))
         .collect::<Vec<_>>()
         .join(";;")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Attributes = BTreeMap<String, String>;
+
+    fn data(value: serde_json::Value) -> data::Tree {
+        data::Tree::new("test", &value.to_string()).unwrap()
+    }
+
+    fn fixture() -> serde_json::Value {
+        serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap()
+    }
+
+    fn tree() -> Tree {
+        tree::build(&data(fixture()), &Default::default()).unwrap()
+    }
+
+    fn render_to_string(render: impl FnOnce(&mut dyn Write) -> crate::Result<()>) -> String {
+        let mut output = Vec::new();
+        render(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Attributes of all elements with an id, by their id.
+    fn elements(svg: &str) -> BTreeMap<String, Attributes> {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        doc.descendants()
+            .filter_map(|node| {
+                let attributes = node
+                    .attributes()
+                    .map(|attr| (attr.name().to_owned(), attr.value().to_owned()))
+                    .collect();
+                Some((node.attribute("id")?.to_owned(), attributes))
+            })
+            .collect()
+    }
+
+    fn attribute<'a>(
+        elements: &'a BTreeMap<String, Attributes>,
+        id: &str,
+        name: &str,
+    ) -> Option<&'a str> {
+        let element = elements.get(id).unwrap_or_else(|| panic!("missing {id}"));
+        element.get(name).map(String::as_str)
+    }
+
+    /// Titles of the labels in the group with the class `class`.
+    fn labels(svg: &str, class: &str) -> Vec<String> {
+        let doc = roxmltree::Document::parse(svg).unwrap();
+        doc.descendants()
+            .filter(|node| node.attribute("class") == Some(class))
+            .flat_map(|group| group.descendants())
+            .filter(|node| node.has_tag_name("title"))
+            .filter_map(|node| node.text())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn template() {
+        let mut value = fixture();
+        value["nodes"]["102"]["name"] = r#"Might & <"Main">"#.into();
+        value["nodes"]["102"]["stats"] = serde_json::json!(["a & b\nc", "<d>"]);
+        let tree = tree::build(&data(value), &Default::default()).unwrap();
+
+        let svg = render_to_string(|output| render(&tree, output));
+        let elements = elements(&svg);
+
+        assert_eq!(
+            attribute(&elements, "n102", "data-name"),
+            Some(r#"Might & <"Main">"#)
+        );
+        assert_eq!(
+            attribute(&elements, "n102", "data-stats"),
+            Some("a & b\nc;;<d>")
+        );
+        assert_eq!(attribute(&elements, "n102", "data-kind"), Some("Notable"));
+        assert_eq!(attribute(&elements, "n105", "class"), Some("mastery"));
+        assert_eq!(
+            attribute(&elements, "n1201", "class"),
+            Some("ascendancy Berserker")
+        );
+        assert!(elements.contains_key("c101-102"));
+    }
+
+    #[test]
+    fn build() {
+        let tree = tree();
+        let build = Build {
+            nodes: BTreeSet::from([101, 102, 201, 1000, 1001]),
+            ascendancy: Some(Ascendancy::new("Ascendant")),
+            highlighted: BTreeSet::from([201]),
+            ..Default::default()
+        };
+
+        let svg = render_to_string(|output| render_build(&tree, &build, output));
+        let elements = elements(&svg);
+
+        assert_eq!(attribute(&elements, "n101", "fill"), Some(ACTIVE_COLOR));
+        assert_eq!(attribute(&elements, "n201", "fill"), Some(HIGHLIGHT_COLOR));
+        assert_eq!(attribute(&elements, "n103", "fill"), Some(DEFAULT_COLOR));
+        assert_eq!(attribute(&elements, "n1001", "fill"), Some(ACTIVE_COLOR));
+        assert_eq!(
+            attribute(&elements, "c101-102", "stroke"),
+            Some(ACTIVE_COLOR)
+        );
+        assert_eq!(
+            attribute(&elements, "c101-201", "stroke"),
+            Some(HIGHLIGHT_COLOR)
+        );
+        assert_eq!(attribute(&elements, "c101-103", "stroke"), None);
+
+        // Unallocated masteries and other ascendancies are not rendered.
+        for id in ["n105", "n1101", "n1201", "n1501"] {
+            assert!(!elements.contains_key(id), "{id}");
+        }
+    }
+
+    #[test]
+    fn timeline_numbered() {
+        let tree = tree();
+        let timeline = Timeline::from_nodes(&tree, [101, 102, 201, 102]);
+        assert_eq!(timeline.steps, [vec![101], vec![102], vec![201]]);
+
+        let svg = render_to_string(|output| {
+            render_timeline(&tree, &timeline, TimelineStyle::Numbered, output)
+        });
+        let elements = elements(&svg);
+
+        assert_eq!(labels(&svg, "steps"), ["1", "2", "3"]);
+        assert_eq!(attribute(&elements, "n201", "fill"), Some(ACTIVE_COLOR));
+        assert_eq!(
+            attribute(&elements, "c101-201", "stroke"),
+            Some(ACTIVE_COLOR)
+        );
+        assert_eq!(attribute(&elements, "c101-103", "stroke"), None);
+        assert!(!svg.contains("animation"));
+    }
+
+    #[test]
+    fn timeline_animated() {
+        let tree = tree();
+        let timeline = Timeline::from_builds([
+            Build {
+                nodes: BTreeSet::from([101]),
+                ..Default::default()
+            },
+            Build {
+                nodes: BTreeSet::from([101, 102, 201]),
+                ..Default::default()
+            },
+        ]);
+        assert_eq!(timeline.steps, [vec![101], vec![102, 201]]);
+
+        let style = TimelineStyle::Animated { step_duration: 0.5 };
+        let svg = render_to_string(|output| render_timeline(&tree, &timeline, style, output));
+        let elements = elements(&svg);
+
+        for rule in [
+            "#n101 { animation: reveal-node 0.5s 0s forwards }",
+            "#n102, #n201 { animation: reveal-node 0.5s 0.5s forwards }",
+            "#c101-102, #c101-201 { animation: reveal-connection 0.5s 0.5s forwards }",
+        ] {
+            assert!(svg.contains(rule), "{rule}");
+        }
+        // Allocated nodes are revealed by the animation.
+        assert_eq!(attribute(&elements, "n102", "fill"), Some(DEFAULT_COLOR));
+        assert_eq!(
+            attribute(&elements, "c101-102", "stroke"),
+            Some(DEFAULT_COLOR)
+        );
+        assert!(labels(&svg, "steps").is_empty());
+    }
+
+    #[test]
+    fn comparison() {
+        let tree = tree();
+        let a = Build {
+            nodes: BTreeSet::from([101, 102, 103, 1000, 1001]),
+            ascendancy: Some(Ascendancy::new("Ascendant")),
+            ..Default::default()
+        };
+        let b = Build {
+            nodes: BTreeSet::from([101, 103, 201]),
+            ascendancy: Some(Ascendancy::new("Berserker")),
+            ..Default::default()
+        };
+
+        let svg = render_to_string(|output| render_comparison(&tree, &a, &b, output));
+        let elements = elements(&svg);
+
+        assert_eq!(attribute(&elements, "n101", "fill"), Some(BOTH_COLOR));
+        assert_eq!(attribute(&elements, "n102", "fill"), Some(ONLY_A_COLOR));
+        assert_eq!(attribute(&elements, "n201", "fill"), Some(ONLY_B_COLOR));
+        assert_eq!(attribute(&elements, "c101-103", "stroke"), Some(BOTH_COLOR));
+        assert_eq!(
+            attribute(&elements, "c101-102", "stroke"),
+            Some(ONLY_A_COLOR)
+        );
+        assert_eq!(
+            attribute(&elements, "c101-201", "stroke"),
+            Some(ONLY_B_COLOR)
+        );
+
+        // Ascendancies of both builds are included.
+        assert_eq!(attribute(&elements, "n1001", "fill"), Some(ONLY_A_COLOR));
+        assert_eq!(attribute(&elements, "n1201", "fill"), Some(DEFAULT_COLOR));
+        assert!(!elements.contains_key("n1101"));
+    }
+
+    #[test]
+    fn changes() {
+        let old = fixture();
+        let mut new = fixture();
+        new["nodes"]["102"]["name"] = r#"Might & <"Main">"#.into();
+        new["nodes"]["106"] = serde_json::json!({
+            "name": "Dexterity",
+            "stats": ["+10 to Dexterity"],
+            "group": 1, "orbit": 1, "orbitIndex": 2,
+            "out": [], "in": ["101"],
+        });
+        new["nodes"]["101"]["out"] = serde_json::json!(["102", "103", "106"]);
+        new["groups"]["1"]["nodes"]
+            .as_array_mut()
+            .unwrap()
+            .push("106".into());
+        new["nodes"].as_object_mut().unwrap().remove("201");
+        new["nodes"]["200"]["out"] = serde_json::json!([]);
+        new["groups"]["2"]["nodes"] = serde_json::json!(["200"]);
+        let (old, new) = (data(old), data(new));
+
+        let diff = diff::diff(&old, &new).unwrap();
+        let svg = render_to_string(|output| render_diff(&old, &new, &diff, output));
+        let elements = elements(&svg);
+
+        assert_eq!(attribute(&elements, "n101", "fill"), Some(DEFAULT_COLOR));
+        assert_eq!(attribute(&elements, "n101", "data-change"), None);
+
+        assert_eq!(attribute(&elements, "n102", "fill"), Some(CHANGED_COLOR));
+        assert_eq!(attribute(&elements, "n102", "data-change"), Some("renamed"));
+        assert_eq!(
+            attribute(&elements, "n102", "data-name"),
+            Some(r#"Might & <"Main">"#)
+        );
+        assert_eq!(attribute(&elements, "n102", "data-old-name"), Some("Might"));
+        assert_eq!(
+            attribute(&elements, "n102", "data-old-stats"),
+            Some("10% increased Damage")
+        );
+
+        assert_eq!(attribute(&elements, "n106", "fill"), Some(ADDED_COLOR));
+        assert_eq!(
+            attribute(&elements, "c101-106", "stroke"),
+            Some(ADDED_COLOR)
+        );
+
+        assert_eq!(attribute(&elements, "n201", "fill"), Some(REMOVED_COLOR));
+        assert_eq!(
+            attribute(&elements, "c101-201", "stroke"),
+            Some(REMOVED_COLOR)
+        );
+        assert_eq!(attribute(&elements, "c101-102", "stroke"), None);
+    }
+
+    #[test]
+    fn heatmap() {
+        let data = data(fixture());
+        let tree = tree::build(&data, &heatmap::options()).unwrap();
+        let mut heatmap = Heatmap::default();
+        heatmap.add(&tree, &BTreeSet::from([101, 102, 201, 1200, 1201]));
+        heatmap.add(&tree, &BTreeSet::from([101, 102]));
+
+        let svg = render_to_string(|output| render_heatmap(&data, &heatmap, output));
+        let elements = elements(&svg);
+
+        assert_eq!(
+            attribute(&elements, "n102", "fill"),
+            Some(&*heat_color(1.0))
+        );
+        assert_eq!(attribute(&elements, "n102", "data-count"), Some("2"));
+        assert_eq!(
+            attribute(&elements, "n201", "fill"),
+            Some(&*heat_color(0.5))
+        );
+        assert_eq!(attribute(&elements, "n201", "data-count"), Some("1"));
+        assert_eq!(attribute(&elements, "n103", "fill"), Some(DEFAULT_COLOR));
+        assert_eq!(attribute(&elements, "n103", "data-count"), Some("0"));
+        assert_eq!(attribute(&elements, "c101-102", "data-count"), Some("2"));
+        assert_eq!(attribute(&elements, "c101-102", "stroke-width"), Some("60"));
+        assert_eq!(attribute(&elements, "c101-201", "stroke-width"), Some("40"));
+        assert_eq!(attribute(&elements, "c101-103", "data-count"), None);
+
+        // Only allocated ascendancies are included.
+        assert_eq!(attribute(&elements, "n1201", "data-count"), Some("1"));
+        assert!(!elements.contains_key("n1101"));
+
+        assert_eq!(labels(&svg, "legend"), ["0", "2 of 2 builds"]);
+    }
+}
@@ -1,9 +1,14 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::f32::consts::PI;
+use std::sync::Arc;
+
+use serde::Deserialize;
 
 use crate::data;
 
 const TWO_PI: f32 = 2.0 * PI;
+/// Small border around the view box to make positioning the image easier.
+const BORDER: i32 = 75;
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Coord {
@@ -11,15 +16,17 @@ pub struct Coord {
     pub y: i32,
 }
 
+/// A node of the rendered tree.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
 pub struct Node {
-    pub id: u16,
+    pub id: u32,
     pub position: Coord,
     pub kind: NodeKind,
     pub meta: NodeMeta,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum NodeKind {
     Jewel,
     Normal,
@@ -58,79 +65,173 @@ pub enum AscendancyNodeKind {
     Notable,
 }
 
-#[derive(
-    Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, strum::EnumString, strum::AsRefStr,
-)]
-pub enum Ascendancy {
-    Ascendant,
-    Juggernaut,
-    Berserker,
-    Chieftain,
-    Raider,
-    Deadeye,
-    Pathfinder,
-    Occultist,
-    Elementalist,
-    Necromancer,
-    Slayer,
-    Gladiator,
-    Champion,
-    Inquisitor,
-    Hierophant,
-    Guardian,
-    Assassin,
-    Trickster,
-    Saboteur,
-    // 3.23 Ascendancies
-    Warden,
-    Warlock,
-    Primalist,
-    // 3.27
-    Aul,
-    Breachlord,
-    Catarina,
-    Delirious,
-    Farrul,
-    KingInTheMists,
-    Lycia,
-    Olroth,
-    Oshabi,
-    Trialmaster,
-}
+/// Name of an ascendancy, as referenced by `ascendancyName` of ascendancy nodes.
+///
+/// Names are shared between all nodes of an ascendancy, which keeps cloning cheap.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ascendancy(Arc<str>);
 
 impl Ascendancy {
-    pub fn is_alternate(self) -> bool {
-        matches!(self, Self::Warden | Self::Warlock | Self::Primalist)
+    pub fn new(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+impl AsRef<str> for Ascendancy {
+    fn as_ref(&self) -> &str {
+        &self.0
     }
 }
 
+/// Where ascendancies are placed in the rendered tree.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum AscendancyLayout {
+    /// Keep the positions from the tree data.
+    Original,
+    /// All ascendancies on top of each other in the top right corner,
+    /// alternate ascendancies mirrored in the top left corner.
+    #[default]
+    Stacked,
+    /// All ascendancies side by side in a grid above the tree.
+    Grid,
+    /// Ascendancies outside the tree, in the direction of their class start.
+    ///
+    /// Alternate ascendancies and bloodlines do not belong to a class, they are
+    /// placed in a row above the tree instead, together with the ascendancies of
+    /// classes starting in the center like the Scion.
+    ClassStart,
+}
+
+/// Options for [`build`], configured per tree in `config.toml`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Options {
+    pub ascendancy_layout: AscendancyLayout,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Where ascendancies are placed.
+    pub fn ascendancy_layout(mut self, layout: AscendancyLayout) -> Self {
+        self.ascendancy_layout = layout;
+        self
+    }
+}
+
+/// Where an ascendancy is defined in the tree data.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum AscendancySource {
+    /// Ascendancy of a class, both indices as used in tree URLs.
+    Class { class: usize, ascendancy: usize },
+    /// Alternate ascendancy, available to all classes, index as used in tree URLs.
+    ///
+    /// Replaces the class ascendancy, e.g. the 3.23 Warden, Warlock and Primalist.
+    Alternate { ascendancy: usize },
+    /// Bloodline, an alternate ascendancy allocated in addition to the class ascendancy,
+    /// e.g. the 3.27 bloodlines.
+    Bloodline { ascendancy: usize },
+}
+
+impl AscendancySource {
+    fn find(tree: &data::Tree, ascendancy: &Ascendancy) -> Option<Self> {
+        let name = ascendancy.as_ref();
+
+        let class = tree
+            .data
+            .classes
+            .iter()
+            .enumerate()
+            .find_map(|(class_i, class)| {
+                class
+                    .ascendancies
+                    .iter()
+                    .position(|asc| asc.name == name)
+                    .map(|asc_i| Self::Class {
+                        class: class_i,
+                        ascendancy: asc_i + 1,
+                    })
+            });
+
+        class.or_else(|| {
+            tree.data
+                .alternate_ascendancies
+                .iter()
+                .position(|asc| asc.id == name)
+                .map(|i| {
+                    if tree.is_bloodline(name) {
+                        Self::Bloodline { ascendancy: i + 1 }
+                    } else {
+                        Self::Alternate { ascendancy: i + 1 }
+                    }
+                })
+        })
+    }
+}
+
+/// End of a [`Connection`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct NodeRef {
-    pub id: u16,
+    pub id: u32,
     pub position: Coord,
     pub kind: NodeKind,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+impl From<&Node> for NodeRef {
+    fn from(node: &Node) -> Self {
+        Self {
+            id: node.id,
+            position: node.position,
+            kind: node.kind.clone(),
+        }
+    }
+}
+
+/// A connection between two nodes of the rendered tree.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
 pub struct Connection {
     pub a: NodeRef,
     pub b: NodeRef,
     pub path: Path,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// Shape of a [`Connection`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
 pub enum Path {
     Arc { sweep: Sweep, radius: u32 },
     Line {},
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+impl Path {
+    /// Arc along an orbit of `radius` from the node at `angle` to the node at `out_angle`.
+    pub fn arc(radius: u32, angle: f32, out_angle: f32) -> Self {
+        let rot = (angle - out_angle + TWO_PI) % TWO_PI;
+        let sweep = if rot > PI {
+            Sweep::Clockwise
+        } else {
+            Sweep::CounterClockwise
+        };
+
+        Path::Arc { sweep, radius }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Sweep {
     Clockwise,
     CounterClockwise,
 }
 
-#[derive(Debug)]
+/// Bounds of the rendered tree.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ViewBox {
     pub x: i32,
     pub y: i32,
@@ -138,23 +239,119 @@ pub struct ViewBox {
     pub dy: u32,
 }
 
-#[derive(Debug)]
+impl ViewBox {
+    /// Grows the view box to contain `coord` including the border.
+    pub fn extend(&mut self, coord: Coord) {
+        let min_x = self.x.min(coord.x - BORDER);
+        let min_y = self.y.min(coord.y - BORDER);
+        let max_x = (self.x + self.dx as i32).max(coord.x + BORDER);
+        let max_y = (self.y + self.dy as i32).max(coord.y + BORDER);
+
+        self.x = min_x;
+        self.y = min_y;
+        self.dx = (max_x - min_x) as u32;
+        self.dy = (max_y - min_y) as u32;
+    }
+
+    /// Smallest view box containing both view boxes.
+    pub fn union(&self, other: &ViewBox) -> ViewBox {
+        let min_x = self.x.min(other.x);
+        let min_y = self.y.min(other.y);
+        let max_x = (self.x + self.dx as i32).max(other.x + other.dx as i32);
+        let max_y = (self.y + self.dy as i32).max(other.y + other.dy as i32);
+
+        ViewBox {
+            x: min_x,
+            y: min_y,
+            dx: (max_x - min_x) as u32,
+            dy: (max_y - min_y) as u32,
+        }
+    }
+}
+
+/// A passive tree laid out for rendering, see [`build`].
+///
+/// Nodes and connections are sorted, which keeps the rendered output stable.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Tree {
     pub view_box: ViewBox,
     pub nodes: Vec<Node>,
     pub connections: Vec<Connection>,
     pub ascendancies: BTreeMap<Ascendancy, AscendancyInfo>,
     pub alternate_ascendancies: BTreeSet<(Ascendancy, AscendancyInfo)>,
+    /// Alternate ascendancies which are bloodlines, they are allocated in addition
+    /// to the class ascendancy instead of replacing it.
+    pub bloodlines: BTreeSet<Ascendancy>,
+    /// Class start nodes, which are not rendered and therefore not part of `nodes`.
+    pub class_starts: Vec<ClassStart>,
+    /// Notables of the group of each mastery, keyed by mastery node id.
+    ///
+    /// Masteries are not connected to other nodes, they can be allocated
+    /// once a notable of their group is allocated.
+    pub masteries: BTreeMap<u32, Vec<u32>>,
 }
 
+impl Tree {
+    /// Looks up a node by its id.
+    pub fn node(&self, id: u32) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Nodes with the id `query` or a name which contains `query`, ignoring case.
+    pub fn search(&self, query: &str) -> Vec<&Node> {
+        let id = query.parse::<u32>().ok();
+        let name = query.to_lowercase();
+
+        self.nodes
+            .iter()
+            .filter(|node| match id {
+                Some(id) => node.id == id,
+                None => node.meta.name.to_lowercase().contains(&name),
+            })
+            .collect()
+    }
+
+    /// Looks up the ascendancy by the class and ascendancy ids used in tree URLs.
+    pub fn ascendancy(&self, class: u8, ascendancy: u8) -> Option<Ascendancy> {
+        self.ascendancies
+            .iter()
+            .find(|(_, info)| info.class == class && info.ascendancy == ascendancy)
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Looks up the alternate ascendancy by the class and alternate ascendancy ids used in tree URLs.
+    pub fn alternate_ascendancy(&self, class: u8, ascendancy: u8) -> Option<Ascendancy> {
+        self.alternate_ascendancies
+            .iter()
+            .find(|(_, info)| info.class == class && info.ascendancy == ascendancy)
+            .map(|(name, _)| name.clone())
+    }
+}
+
+/// Start node of a class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClassStart {
+    /// Class id as used in tree URLs.
+    pub class: u8,
+    pub id: u32,
+    pub position: Coord,
+    /// Nodes connected to the class start, in either direction.
+    pub connections: Vec<u32>,
+}
+
+/// Ids of an ascendancy as used in tree URLs.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
 pub struct AscendancyInfo {
     pub class: u8,
     pub ascendancy: u8,
     pub start_node: u16,
 }
 
-pub fn build(tree: &data::Tree) -> Tree {
+/// Lays out the tree data for rendering.
+pub fn build(tree: &data::Tree, options: &Options) -> crate::Result<Tree> {
     let mut min_x = i32::MAX;
     let mut min_y = i32::MAX;
     let mut max_x = i32::MIN;
@@ -171,20 +368,48 @@ pub fn build(tree: &data::Tree) -> Tree {
     let mut connections = Vec::new();
 
     let mut tmp_ascendancies = BTreeMap::new();
-    #[derive(Default)]
-    struct TmpAsc {
-        start_node: u16,
-        start_position: Coord,
-        nodes: Vec<Node>,
-        connections: Vec<Connection>,
-    }
+    let mut class_starts = BTreeMap::new();
+    let mut class_start_nodes = Vec::new();
+    let mut class_start_connections = BTreeMap::<u32, BTreeSet<u32>>::new();
+    let mut masteries = BTreeMap::new();
 
     for group in tree.groups().filter(filter_group) {
-        for node in group.nodes().filter(filter_node) {
-            let (angle, x, y) = node.position();
+        let mut group_masteries = Vec::new();
+        let mut group_notables = Vec::new();
+
+        for node in group.nodes() {
+            let node = node?;
+            let (angle, x, y) = node.position()?;
+
+            if let Some(class) = node.class_start_index {
+                class_starts.insert(class as usize, Coord { x, y });
+                class_start_nodes.push(ClassStart {
+                    class: class as u8,
+                    id: node.id().into(),
+                    position: Coord { x, y },
+                    connections: Vec::new(),
+                });
+
+                let connections = class_start_connections.entry(node.id().into()).or_default();
+                for out_node in node.out() {
+                    let out_node = out_node?;
+                    if filter_class_start_connection(&out_node) {
+                        connections.insert(out_node.id().into());
+                    }
+                }
+            }
+            if !filter_node(&node) {
+                continue;
+            }
+
+            if node.is_mastery {
+                group_masteries.push(u32::from(node.id()));
+            } else if node.is_notable && node.ascendancy_name.is_none() {
+                group_notables.push(u32::from(node.id()));
+            }
 
             let tree_node = Node {
-                id: node.id(),
+                id: node.id().into(),
                 position: Coord { x, y },
                 kind: node_kind(&node),
                 meta: NodeMeta {
@@ -196,7 +421,7 @@ pub fn build(tree: &data::Tree) -> Tree {
             let (nodes, connections) =
                 if let NodeKind::Ascendancy { ascendancy, .. } = &tree_node.kind {
                     let asc = tmp_ascendancies
-                        .entry(*ascendancy)
+                        .entry(ascendancy.clone())
                         .or_insert_with(TmpAsc::default);
                     if node.is_ascendancy_start {
                         asc.start_node = node.id();
@@ -210,35 +435,39 @@ pub fn build(tree: &data::Tree) -> Tree {
                     (&mut nodes, &mut connections)
                 };
 
-            for out_node in node
-                .out()
-                .filter(|out_node| filter_connection(&node, out_node))
-            {
-                let (out_angle, out_x, out_y) = out_node.position();
-
-                let path = if node.group == out_node.group && node.orbit == out_node.orbit {
-                    let radius = tree.data.constants.orbit_radii[node.orbit.unwrap() as usize];
-
-                    let rot = (angle - out_angle + TWO_PI) % TWO_PI;
-                    let sweep = if rot > PI {
-                        Sweep::Clockwise
-                    } else {
-                        Sweep::CounterClockwise
-                    };
-
-                    Path::Arc { sweep, radius }
-                } else {
-                    Path::Line {}
+            for out_node in node.out() {
+                let out_node = out_node?;
+                if out_node.class_start_index.is_some() && filter_class_start_connection(&node) {
+                    class_start_connections
+                        .entry(out_node.id().into())
+                        .or_default()
+                        .insert(node.id().into());
+                }
+                if !filter_connection(&node, &out_node) {
+                    continue;
+                }
+                let (out_angle, out_x, out_y) = out_node.position()?;
+
+                let radius = node
+                    .orbit
+                    .and_then(|orbit| tree.orbit_radius(orbit as usize));
+                let path = match radius {
+                    Some(radius)
+                        if node.group == out_node.group && node.orbit == out_node.orbit =>
+                    {
+                        Path::arc(radius, angle, out_angle)
+                    }
+                    _ => Path::Line {},
                 };
 
                 let connection = Connection {
                     a: NodeRef {
                         id: tree_node.id,
                         position: tree_node.position,
-                        kind: tree_node.kind,
+                        kind: tree_node.kind.clone(),
                     },
                     b: NodeRef {
-                        id: out_node.id(),
+                        id: out_node.id().into(),
                         position: Coord { x: out_x, y: out_y },
                         kind: node_kind(&out_node),
                     },
@@ -250,22 +479,44 @@ pub fn build(tree: &data::Tree) -> Tree {
 
             nodes.push(tree_node);
         }
+
+        for mastery in group_masteries {
+            masteries.insert(mastery, group_notables.clone());
+        }
     }
 
-    const ASCENDANCY_POS_X: i32 = 7000;
-    const ASCENDANCY_POS_Y: i32 = -7700;
+    min_x -= BORDER;
+    min_y -= BORDER;
+    max_x += BORDER;
+    max_y += BORDER;
+
+    let mut view_box = ViewBox {
+        x: min_x,
+        y: min_y,
+        dx: (max_x - min_x) as u32,
+        dy: (max_y - min_y) as u32,
+    };
+
+    let mut tmp_ascendancies = tmp_ascendancies
+        .into_iter()
+        .map(|(name, asc)| {
+            let source = AscendancySource::find(tree, &name);
+            (name, source, asc)
+        })
+        .collect::<Vec<_>>();
+    // Class ascendancies in class order first, then alternate ascendancies, then unknown.
+    tmp_ascendancies.sort_by_key(|(_, source, _)| (source.is_none(), *source));
+
+    let layout = Layout::new(options, &view_box, &nodes, &class_starts, &tmp_ascendancies);
 
     let mut ascendancies = BTreeMap::new();
     let mut alternate_ascendancies = BTreeSet::new();
+    let mut bloodlines = BTreeSet::new();
 
-    for (asc_name, asc) in tmp_ascendancies.into_iter() {
-        let pos_x_offset = if asc_name.is_alternate() {
-            -ASCENDANCY_POS_X
-        } else {
-            ASCENDANCY_POS_X
-        };
-        let diff_x = pos_x_offset - asc.start_position.x;
-        let diff_y = ASCENDANCY_POS_Y - asc.start_position.y;
+    for (i, (asc_name, source, asc)) in tmp_ascendancies.into_iter().enumerate() {
+        let position = layout.position(i, source, asc.start_position);
+        let diff_x = position.x - asc.start_position.x;
+        let diff_y = position.y - asc.start_position.y;
 
         macro_rules! update_node {
             ($node:expr) => {
@@ -278,7 +529,7 @@ pub fn build(tree: &data::Tree) -> Tree {
 
         for mut node in asc.nodes {
             update_node!(node);
-            update_min_max(node.position.x, node.position.y);
+            view_box.extend(node.position);
             nodes.push(node);
         }
 
@@ -288,83 +539,218 @@ pub fn build(tree: &data::Tree) -> Tree {
             connections.push(connection);
         }
 
-        if asc_name.is_alternate() {
-            let ascendancy = tree
-                .data
-                .alternate_ascendancies
-                .iter()
-                .enumerate()
-                .find_map(|(i, asc)| (asc.id == asc_name.as_ref()).then_some(i + 1))
-                .unwrap_or_else(|| panic!(
-                    "expected to find alternate ascendancy {asc_name:?} in the alternate ascendancy array"
-                ));
-
-            for (class, _) in tree.data.classes.iter().enumerate() {
-                alternate_ascendancies.insert((
+        match source {
+            Some(AscendancySource::Class { class, ascendancy }) => {
+                ascendancies.insert(
                     asc_name,
                     AscendancyInfo {
                         class: class as u8,
                         ascendancy: ascendancy as u8,
                         start_node: asc.start_node,
                     },
-                ));
+                );
+            }
+            Some(
+                AscendancySource::Alternate { ascendancy }
+                | AscendancySource::Bloodline { ascendancy },
+            ) => {
+                if matches!(source, Some(AscendancySource::Bloodline { .. })) {
+                    bloodlines.insert(asc_name.clone());
+                }
+                for (class, _) in tree.data.classes.iter().enumerate() {
+                    alternate_ascendancies.insert((
+                        asc_name.clone(),
+                        AscendancyInfo {
+                            class: class as u8,
+                            ascendancy: ascendancy as u8,
+                            start_node: asc.start_node,
+                        },
+                    ));
+                }
             }
-            continue;
+            // Neither a class nor an alternate ascendancy, the nodes are still part
+            // of the tree but the ascendancy cannot be selected.
+            None => {}
         }
-
-        let (class, ascendancy) = tree
-            .data
-            .classes
-            .iter()
-            .enumerate()
-            .find_map(|(class_i, class)| {
-                class
-                    .ascendancies
-                    .iter()
-                    .enumerate()
-                    .find(|(_, asc)| asc.name == asc_name.as_ref())
-                    .map(|(asc_i, _)| (class_i, asc_i + 1))
-            })
-            .unwrap_or((0, 0));
-
-        ascendancies.insert(
-            asc_name,
-            AscendancyInfo {
-                class: class as u8,
-                ascendancy: ascendancy as u8,
-                start_node: asc.start_node,
-            },
-        );
     }
 
-    // Small border to make positioning the image easier
-    min_x -= 75;
-    min_y -= 75;
-    max_x += 75;
-    max_y += 75;
-
-    let dx = (max_x - min_x) as u32;
-    let dy = (max_y - min_y) as u32;
-
     nodes.sort();
     connections.sort();
 
-    Tree {
-        view_box: ViewBox {
-            x: min_x,
-            y: min_y,
-            dx,
-            dy,
-        },
+    for start in &mut class_start_nodes {
+        if let Some(connections) = class_start_connections.remove(&start.id) {
+            start.connections = connections.into_iter().collect();
+        }
+    }
+    class_start_nodes.sort_by_key(|start| start.class);
+
+    Ok(Tree {
+        view_box,
         nodes,
         connections,
         ascendancies,
         alternate_ascendancies,
+        bloodlines,
+        class_starts: class_start_nodes,
+        masteries,
+    })
+}
+
+#[derive(Default)]
+struct TmpAsc {
+    start_node: u16,
+    start_position: Coord,
+    nodes: Vec<Node>,
+    connections: Vec<Connection>,
+}
+
+/// Positions of the ascendancy start nodes for an [`AscendancyLayout`].
+struct Layout<'a> {
+    options: &'a Options,
+    /// Top left corner of the grid.
+    grid: Coord,
+    grid_columns: usize,
+    /// Size of a grid cell, large enough to fit every ascendancy.
+    cell: i32,
+    /// Position of each ascendancy for [`AscendancyLayout::ClassStart`].
+    class_start: Vec<Coord>,
+}
+
+impl<'a> Layout<'a> {
+    const ASCENDANCY_POS_X: i32 = 7000;
+    const ASCENDANCY_POS_Y: i32 = -7700;
+    // Bloodlines are shown together with the class ascendancy, place them right next to it.
+    const BLOODLINE_POS_X: i32 = Self::ASCENDANCY_POS_X + 3500;
+    /// Space between ascendancies and the tree.
+    const GAP: i32 = 500;
+
+    fn new(
+        options: &'a Options,
+        view_box: &ViewBox,
+        nodes: &[Node],
+        class_starts: &BTreeMap<usize, Coord>,
+        ascendancies: &[(Ascendancy, Option<AscendancySource>, TmpAsc)],
+    ) -> Self {
+        let extent = ascendancies
+            .iter()
+            .flat_map(|(_, _, asc)| {
+                asc.nodes
+                    .iter()
+                    .map(|node| distance(node.position, asc.start_position))
+            })
+            .fold(0.0, f32::max);
+        let cell = (extent * 2.0) as i32 + Self::GAP;
+
+        let grid_columns = (ascendancies.len() as f32).sqrt().ceil().max(1.0) as usize;
+        let grid_rows = ascendancies.len().div_ceil(grid_columns) as i32;
+        let grid = Coord {
+            x: -(grid_columns as i32 * cell) / 2,
+            y: view_box.y - Self::GAP - grid_rows * cell,
+        };
+
+        let tree_radius = nodes
+            .iter()
+            .map(|node| distance(node.position, Coord::default()))
+            .fold(0.0, f32::max);
+
+        let mut class_counts = BTreeMap::<usize, usize>::new();
+        for (_, source, _) in ascendancies {
+            if let Some(AscendancySource::Class { class, .. }) = source {
+                *class_counts.entry(*class).or_default() += 1;
+            }
+        }
+
+        // Ascendancies of a class are placed next to each other outside the tree,
+        // in the direction of the class start.
+        let distance_from_center = tree_radius + (Self::GAP + cell / 2) as f32;
+        let mut class_indices = BTreeMap::<usize, usize>::new();
+        let radial: Vec<_> = ascendancies
+            .iter()
+            .map(|(_, source, _)| {
+                let Some(AscendancySource::Class { class, .. }) = source else {
+                    return None;
+                };
+                let index = class_indices.entry(*class).or_default();
+                *index += 1;
+
+                let start = class_starts.get(class).copied().unwrap_or_default();
+                let length = distance(start, Coord::default());
+                if length < 1.0 {
+                    return None;
+                }
+                let (dir_x, dir_y) = (start.x as f32 / length, start.y as f32 / length);
+
+                // Spread ascendancies of the same class perpendicular to the direction.
+                let offset =
+                    (*index as f32 - 1.0 - (class_counts[class] - 1) as f32 / 2.0) * cell as f32;
+
+                Some(Coord {
+                    x: (dir_x * distance_from_center - dir_y * offset) as i32,
+                    y: (dir_y * distance_from_center + dir_x * offset) as i32,
+                })
+            })
+            .collect();
+
+        // All other ascendancies are placed in a row above everything else.
+        let outer_radius = radial
+            .iter()
+            .flatten()
+            .map(|&position| distance(position, Coord::default()) + (cell / 2) as f32)
+            .fold(tree_radius, f32::max);
+        let row_y = -(outer_radius as i32 + Self::GAP + cell / 2);
+        let row_count = radial.iter().filter(|position| position.is_none()).count();
+        let mut row_index = 0;
+        let class_start = radial
+            .into_iter()
+            .map(|position| {
+                position.unwrap_or_else(|| {
+                    let x = (2 * row_index + 1 - row_count as i32) * cell / 2;
+                    row_index += 1;
+                    Coord { x, y: row_y }
+                })
+            })
+            .collect();
+
+        Self {
+            options,
+            grid,
+            grid_columns,
+            cell,
+            class_start,
+        }
+    }
+
+    /// Position of the start node of the `i`-th ascendancy.
+    fn position(&self, i: usize, source: Option<AscendancySource>, original: Coord) -> Coord {
+        match (self.options.ascendancy_layout, source) {
+            (AscendancyLayout::Original, _) => original,
+            (AscendancyLayout::Grid, _) => Coord {
+                x: self.grid.x + (i % self.grid_columns) as i32 * self.cell + self.cell / 2,
+                y: self.grid.y + (i / self.grid_columns) as i32 * self.cell + self.cell / 2,
+            },
+            (AscendancyLayout::ClassStart, _) => self.class_start[i],
+            (AscendancyLayout::Stacked, source) => {
+                let x = match source {
+                    Some(AscendancySource::Alternate { .. }) => -Self::ASCENDANCY_POS_X,
+                    Some(AscendancySource::Bloodline { .. }) => Self::BLOODLINE_POS_X,
+                    _ => Self::ASCENDANCY_POS_X,
+                };
+
+                Coord {
+                    x,
+                    y: Self::ASCENDANCY_POS_Y,
+                }
+            }
+        }
     }
 }
 
+fn distance(a: Coord, b: Coord) -> f32 {
+    ((a.x - b.x) as f32).hypot((a.y - b.y) as f32)
+}
+
 fn node_kind(node: &data::Node) -> NodeKind {
-    if node.ascendancy_name.is_some() {
+    if let Some(ascendancy) = &node.ascendancy_name {
         // Check ascendancies first, an ascendancy can also be a Jewel socket, etc.
         NodeKind::Ascendancy {
             kind: match (node.is_ascendancy_start, node.is_notable) {
@@ -372,11 +758,7 @@ fn node_kind(node: &data::Node) -> NodeKind {
                 (_, true) => AscendancyNodeKind::Notable,
                 (_, false) => AscendancyNodeKind::Normal,
             },
-            ascendancy: dbg!(&node.ascendancy_name)
-                .as_ref()
-                .expect("ascendancy node should have an ascendancy name")
-                .parse()
-                .expect("invalid/unknown ascendancy name"),
+            ascendancy: Ascendancy::new(ascendancy),
         }
     } else if node.is_keystone {
         NodeKind::Keystone
@@ -398,6 +780,7 @@ fn filter_group(group: &data::Group) -> bool {
 fn filter_node(node: &data::Node) -> bool {
     node.class_start_index.is_none()
 }
+
 fn filter_connection(a: &data::Node, b: &data::Node) -> bool {
     filter_node(b)
         && !a.is_mastery
@@ -405,3 +788,169 @@ fn filter_connection(a: &data::Node, b: &data::Node) -> bool {
         // make sure there are no connections between ascendancy and non ascendancy nodes
         && (a.ascendancy_name.is_some() == b.ascendancy_name.is_some())
 }
+
+/// Class starts only connect to the main tree, see [`filter_connection`].
+fn filter_class_start_connection(node: &data::Node) -> bool {
+    filter_node(node) && !node.is_mastery && node.ascendancy_name.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree_data() -> data::Tree {
+        data::Tree::new("test", include_str!("../tests/fixtures/tree.json")).unwrap()
+    }
+
+    fn start(tree: &Tree, name: &str) -> Coord {
+        tree.nodes
+            .iter()
+            .find(|node| {
+                matches!(
+                    &node.kind,
+                    NodeKind::Ascendancy { kind: AscendancyNodeKind::Start, ascendancy }
+                        if ascendancy.as_ref() == name
+                )
+            })
+            .unwrap()
+            .position
+    }
+
+    #[test]
+    fn search() {
+        let tree = build(&tree_data(), &Default::default()).unwrap();
+        let ids = |query| Vec::from_iter(tree.search(query).iter().map(|node| node.id));
+
+        assert_eq!(tree.node(102).unwrap().meta.name, "Might");
+        assert!(tree.node(100).is_none());
+        assert_eq!(ids("102"), [102]);
+        assert_eq!(ids("mIGHT"), [102]);
+        assert_eq!(ids("jewel"), [103]);
+        assert_eq!(ids("path of"), [1001]);
+        assert!(ids("999").is_empty());
+    }
+
+    #[test]
+    fn ascendancies() {
+        let tree = build(&tree_data(), &Options::new()).unwrap();
+
+        let names: Vec<_> = tree.ascendancies.keys().map(AsRef::as_ref).collect();
+        assert_eq!(names, ["Ascendant", "Berserker", "Juggernaut"]);
+        assert_eq!(tree.ascendancy(1, 1), Some(Ascendancy::new("Juggernaut")));
+
+        let alternate: BTreeSet<_> = tree
+            .alternate_ascendancies
+            .iter()
+            .map(|(name, info)| (name.as_ref(), info.ascendancy))
+            .collect();
+        assert_eq!(
+            alternate,
+            BTreeSet::from([("Aul", 3), ("Warden", 1), ("Warlock", 2)])
+        );
+        assert_eq!(tree.bloodlines, BTreeSet::from([Ascendancy::new("Aul")]));
+    }
+
+    #[test]
+    fn stacked_layout() {
+        let tree = build(&tree_data(), &Options::new()).unwrap();
+
+        let y = Layout::ASCENDANCY_POS_Y;
+        assert_eq!(
+            start(&tree, "Berserker"),
+            Coord {
+                x: Layout::ASCENDANCY_POS_X,
+                y
+            }
+        );
+        assert_eq!(
+            start(&tree, "Warden"),
+            Coord {
+                x: -Layout::ASCENDANCY_POS_X,
+                y
+            }
+        );
+        assert_eq!(
+            start(&tree, "Aul"),
+            Coord {
+                x: Layout::BLOODLINE_POS_X,
+                y
+            }
+        );
+    }
+
+    #[test]
+    fn original_layout() {
+        let options = Options::new().ascendancy_layout(AscendancyLayout::Original);
+        let tree = build(&tree_data(), &options).unwrap();
+
+        assert_eq!(start(&tree, "Berserker"), Coord { x: -6000, y: -5000 });
+        assert_eq!(start(&tree, "Aul"), Coord { x: 6000, y: -5000 });
+    }
+
+    /// Bounding boxes of the main tree (`None`) and of each ascendancy.
+    fn bounds(tree: &Tree) -> BTreeMap<Option<&str>, (Coord, Coord)> {
+        let mut bounds = BTreeMap::new();
+        for node in &tree.nodes {
+            let key = match &node.kind {
+                NodeKind::Ascendancy { ascendancy, .. } => Some(ascendancy.as_ref()),
+                _ => None,
+            };
+            let (min, max) = bounds.entry(key).or_insert((node.position, node.position));
+            min.x = min.x.min(node.position.x);
+            min.y = min.y.min(node.position.y);
+            max.x = max.x.max(node.position.x);
+            max.y = max.y.max(node.position.y);
+        }
+        bounds
+    }
+
+    #[test]
+    fn layouts_do_not_overlap() {
+        // The fixture, and the Marauder starting above the Scion in the center of the tree.
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+        let fixture = data::Tree::new("test", &value.to_string()).unwrap();
+        value["groups"]["2"]["x"] = 0.into();
+        value["groups"]["2"]["y"] = (-1000).into();
+        let above = data::Tree::new("test", &value.to_string()).unwrap();
+
+        for layout in [AscendancyLayout::Grid, AscendancyLayout::ClassStart] {
+            for data in [&fixture, &above] {
+                let tree = build(data, &Options::new().ascendancy_layout(layout)).unwrap();
+                let bounds: Vec<_> = bounds(&tree).into_iter().collect();
+                assert_eq!(bounds.len(), 7, "{layout:?}: {bounds:?}");
+
+                for (i, (a, (a_min, a_max))) in bounds.iter().enumerate() {
+                    for (b, (b_min, b_max)) in &bounds[i + 1..] {
+                        let overlap = a_min.x <= b_max.x
+                            && b_min.x <= a_max.x
+                            && a_min.y <= b_max.y
+                            && b_min.y <= a_max.y;
+                        assert!(!overlap, "{layout:?}: {a:?} overlaps {b:?} in {bounds:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn class_start_layout() {
+        let options = Options::new().ascendancy_layout(AscendancyLayout::ClassStart);
+        let tree = build(&tree_data(), &options).unwrap();
+
+        // The Marauder starts to the right of the center.
+        let berserker = start(&tree, "Berserker");
+        let juggernaut = start(&tree, "Juggernaut");
+        assert!(berserker.x > 1000 && juggernaut.x > 1000);
+        assert_eq!(berserker.x, juggernaut.x);
+
+        // Everything else is in a row above the tree.
+        let row: Vec<_> = ["Ascendant", "Warden", "Warlock", "Aul"]
+            .map(|name| start(&tree, name))
+            .to_vec();
+        assert!(row
+            .iter()
+            .all(|position| position.y == row[0].y && position.y < 0));
+        assert!(row.windows(2).all(|pair| pair[0].x < pair[1].x));
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::allocation::Allocation;
+use crate::data;
+use crate::graph::Graph;
+use crate::tree::{Ascendancy, AscendancyNodeKind, NodeKind, Tree};
+
+/// Result of validating an allocation against a tree version, see [`validate`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Report {
+    /// Name of the tree version.
+    pub version: String,
+    /// Passive points spent outside of the ascendancy.
+    pub points: u32,
+    /// Passive points spent in the ascendancy, the free start node is not included.
+    pub ascendancy_points: u32,
+    /// Passive points spent in the bloodline, they do not count towards the ascendancy points.
+    pub bloodline_points: u32,
+    /// Point limits the allocation was checked against.
+    pub limits: data::Points,
+    /// Limits which are not part of the tree data, they were assumed or not checked.
+    pub notes: Vec<String>,
+    pub violations: Vec<Violation>,
+}
+
+/// A problem with an allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// The class does not exist in the tree version.
+    UnknownClass { class: u8 },
+    /// The ascendancy does not exist for the class.
+    UnknownAscendancy { class: u8, ascendancy: u8 },
+    /// The node does not exist in the tree version.
+    UnknownNode { id: u32 },
+    /// The node is not connected to the class or ascendancy start through allocated nodes.
+    Disconnected { id: u32, name: String },
+    /// The ascendancy node belongs to an ascendancy which is not selected.
+    WrongAscendancy {
+        id: u32,
+        name: String,
+        ascendancy: String,
+    },
+    /// More passive points are spent than available.
+    TooManyPoints { points: u32, limit: u32 },
+    /// More ascendancy points are spent than available.
+    TooManyAscendancyPoints { points: u32, limit: u32 },
+    /// More bloodline points are spent than available.
+    TooManyBloodlinePoints { points: u32, limit: u32 },
+}
+
+/// Validates `allocation` against the tree version `data`, laid out as `tree`.
+///
+/// Cluster jewel nodes can only be checked if they were added to `tree` with
+/// [`crate::cluster::expand`], otherwise they only count towards the points.
+pub fn validate(data: &data::Tree, tree: &Tree, allocation: &Allocation) -> Report {
+    let graph = Graph::new(tree);
+    let nodes: BTreeMap<_, _> = tree.nodes.iter().map(|node| (node.id, node)).collect();
+    let allocated: BTreeSet<_> = allocation
+        .nodes
+        .iter()
+        .map(|&node| u32::from(node))
+        .chain(allocation.cluster_nodes.iter().copied())
+        .collect();
+
+    let mut violations = Vec::new();
+
+    let class_start = graph.class_start(allocation.class);
+    if class_start.is_none() {
+        violations.push(Violation::UnknownClass {
+            class: allocation.class,
+        });
+    }
+
+    let mut ascendancies = Vec::new();
+    if allocation.ascendancy > 0 {
+        match tree.ascendancy(allocation.class, allocation.ascendancy) {
+            Some(ascendancy) => ascendancies.push(ascendancy),
+            None if class_start.is_some() => violations.push(Violation::UnknownAscendancy {
+                class: allocation.class,
+                ascendancy: allocation.ascendancy,
+            }),
+            None => {}
+        }
+    }
+    ascendancies
+        .extend(tree.alternate_ascendancy(allocation.class, allocation.alternate_ascendancy));
+
+    // Nodes connected to the class start or the start of a selected ascendancy.
+    let mut connected = class_start
+        .map(|start| graph.connected(start, &allocated))
+        .unwrap_or_default();
+    for ascendancy in &ascendancies {
+        if let Some(start) = ascendancy_start(tree, ascendancy) {
+            connected.extend(graph.connected(start, &allocated));
+        }
+    }
+
+    let mut points = 0;
+    let mut ascendancy_points = 0;
+    let mut bloodline_points = 0;
+
+    for &id in &allocated {
+        if graph.is_class_start(id) {
+            continue;
+        }
+
+        let Some(node) = nodes.get(&id) else {
+            points += 1;
+            // Unexpanded cluster jewel nodes cannot be checked.
+            if !allocation.cluster_nodes.contains(&id) {
+                violations.push(Violation::UnknownNode { id });
+            }
+            continue;
+        };
+
+        match &node.kind {
+            NodeKind::Ascendancy { kind, ascendancy } => {
+                match kind {
+                    AscendancyNodeKind::Start => {}
+                    _ if tree.bloodlines.contains(ascendancy) => bloodline_points += 1,
+                    _ => ascendancy_points += 1,
+                }
+                if !ascendancies.contains(ascendancy) {
+                    violations.push(Violation::WrongAscendancy {
+                        id,
+                        name: node.meta.name.clone(),
+                        ascendancy: ascendancy.as_ref().to_owned(),
+                    });
+                    continue;
+                }
+            }
+            _ => points += 1,
+        }
+
+        if class_start.is_some() && !connected.contains(&id) {
+            violations.push(Violation::Disconnected {
+                id,
+                name: node.meta.name.clone(),
+            });
+        }
+    }
+
+    let mut notes = Vec::new();
+    let limits = data.points().unwrap_or_else(|| {
+        let limits = data::Points::default();
+        notes.push(format!(
+            "tree {} has no point limits, assuming {} passive and {} ascendancy points",
+            data.version, limits.total_points, limits.ascendancy_points
+        ));
+        limits
+    });
+
+    if points > limits.total_points {
+        violations.push(Violation::TooManyPoints {
+            points,
+            limit: limits.total_points,
+        });
+    }
+    if ascendancy_points > limits.ascendancy_points {
+        violations.push(Violation::TooManyAscendancyPoints {
+            points: ascendancy_points,
+            limit: limits.ascendancy_points,
+        });
+    }
+    match limits.bloodline_points {
+        Some(limit) if bloodline_points > limit => {
+            violations.push(Violation::TooManyBloodlinePoints {
+                points: bloodline_points,
+                limit,
+            });
+        }
+        Some(_) => {}
+        None if bloodline_points > 0 => notes.push(format!(
+            "tree {} has no bloodline point limit, bloodline points are not checked",
+            data.version
+        )),
+        None => {}
+    }
+
+    Report {
+        version: data.version.clone(),
+        points,
+        ascendancy_points,
+        bloodline_points,
+        limits,
+        notes,
+        violations,
+    }
+}
+
+impl Report {
+    /// Whether the allocation has no violations.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tree {}: {} points, {} ascendancy points",
+            self.version, self.points, self.ascendancy_points
+        )?;
+        if self.bloodline_points > 0 {
+            write!(f, ", {} bloodline points", self.bloodline_points)?;
+        }
+        writeln!(f)?;
+        for note in &self.notes {
+            writeln!(f, "note: {note}")?;
+        }
+        for violation in &self.violations {
+            writeln!(f, "{violation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownClass { class } => write!(f, "class {class} does not exist"),
+            Self::UnknownAscendancy { class, ascendancy } => {
+                write!(
+                    f,
+                    "ascendancy {ascendancy} does not exist for class {class}"
+                )
+            }
+            Self::UnknownNode { id } => write!(f, "node {id} does not exist"),
+            Self::Disconnected { id, name } => {
+                write!(f, "node {id} {name} is not connected to the start")
+            }
+            Self::WrongAscendancy {
+                id,
+                name,
+                ascendancy,
+            } => write!(
+                f,
+                "node {id} {name} belongs to {ascendancy}, which is not selected"
+            ),
+            Self::TooManyPoints { points, limit } => {
+                write!(
+                    f,
+                    "{points} passive points spent, only {limit} are available"
+                )
+            }
+            Self::TooManyAscendancyPoints { points, limit } => write!(
+                f,
+                "{points} ascendancy points spent, only {limit} are available"
+            ),
+            Self::TooManyBloodlinePoints { points, limit } => write!(
+                f,
+                "{points} bloodline points spent, only {limit} are available"
+            ),
+        }
+    }
+}
+
+fn ascendancy_start(tree: &Tree, ascendancy: &Ascendancy) -> Option<u32> {
+    tree.nodes
+        .iter()
+        .find(|node| {
+            matches!(
+                &node.kind,
+                NodeKind::Ascendancy { kind: AscendancyNodeKind::Start, ascendancy: a } if a == ascendancy
+            )
+        })
+        .map(|node| node.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trees(points: Option<serde_json::Value>) -> (data::Tree, Tree) {
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/tree.json")).unwrap();
+        if let Some(points) = points {
+            value["points"] = points;
+        }
+        let data = data::Tree::new("test", &value.to_string()).unwrap();
+        let tree = crate::tree::build(&data, &Default::default()).unwrap();
+        (data, tree)
+    }
+
+    /// Berserker with the Aul bloodline, one notable allocated in each.
+    fn allocation() -> Allocation {
+        Allocation {
+            class: 1,
+            ascendancy: 2,
+            alternate_ascendancy: 3,
+            nodes: vec![1201, 1501],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bloodline_points() {
+        let (data, tree) = trees(None);
+
+        let report = validate(&data, &tree, &allocation());
+        assert_eq!(report.violations, Vec::new());
+        assert_eq!(report.ascendancy_points, 1);
+        assert_eq!(report.bloodline_points, 1);
+        assert_eq!(report.limits, data::Points::default());
+        assert_eq!(report.notes.len(), 2, "{:?}", report.notes);
+    }
+
+    #[test]
+    fn too_many_bloodline_points() {
+        let points = serde_json::json!({
+            "totalPoints": 123,
+            "ascendancyPoints": 1,
+            "bloodlinePoints": 0,
+        });
+        let (data, tree) = trees(Some(points));
+
+        let report = validate(&data, &tree, &allocation());
+        assert_eq!(
+            report.violations,
+            vec![Violation::TooManyBloodlinePoints {
+                points: 1,
+                limit: 0
+            }]
+        );
+        assert_eq!(report.notes, Vec::<String>::new());
+    }
+
+    fn scion(nodes: &[u16]) -> Allocation {
+        Allocation {
+            class: 0,
+            ascendancy: 1,
+            nodes: nodes.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn valid() {
+        let (data, tree) = trees(None);
+
+        let report = validate(&data, &tree, &scion(&[101, 102, 201, 1001]));
+        assert!(report.is_valid(), "{report}");
+        assert_eq!(report.version, "test");
+        assert_eq!(report.points, 3);
+        assert_eq!(report.ascendancy_points, 1);
+        assert_eq!(report.bloodline_points, 0);
+        assert_eq!(report.notes.len(), 1, "{:?}", report.notes);
+    }
+
+    #[test]
+    fn masteries_and_jewel_sockets() {
+        let (data, tree) = trees(None);
+
+        let mut allocation = scion(&[101, 102, 103, 105]);
+        // Nodes of a cluster jewel which was not expanded are only counted.
+        allocation.cluster_nodes = vec![65572, 65573];
+        let report = validate(&data, &tree, &allocation);
+        assert!(report.is_valid(), "{report}");
+        assert_eq!(report.points, 6);
+
+        // Masteries need an allocated notable of their group.
+        let report = validate(&data, &tree, &scion(&[101, 103, 105]));
+        assert_eq!(
+            report.violations,
+            vec![Violation::Disconnected {
+                id: 105,
+                name: "Damage Mastery".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn unknown_node() {
+        let (data, tree) = trees(None);
+
+        // E.g. a node which only exists in another tree version.
+        let report = validate(&data, &tree, &scion(&[101, 106]));
+        assert_eq!(report.violations, vec![Violation::UnknownNode { id: 106 }]);
+        assert_eq!(report.points, 2);
+    }
+
+    #[test]
+    fn unknown_class() {
+        let (data, tree) = trees(None);
+
+        let mut allocation = scion(&[]);
+        allocation.ascendancy = 3;
+        let report = validate(&data, &tree, &allocation);
+        assert_eq!(
+            report.violations,
+            vec![Violation::UnknownAscendancy {
+                class: 0,
+                ascendancy: 3
+            }]
+        );
+
+        allocation.class = 7;
+        let report = validate(&data, &tree, &allocation);
+        assert_eq!(
+            report.violations,
+            vec![Violation::UnknownClass { class: 7 }]
+        );
+    }
+
+    #[test]
+    fn disconnected() {
+        let (data, tree) = trees(None);
+
+        // An island of two nodes, connected to each other but not to the start.
+        let report = validate(&data, &tree, &scion(&[102, 103]));
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::Disconnected {
+                    id: 102,
+                    name: "Might".to_owned()
+                },
+                Violation::Disconnected {
+                    id: 103,
+                    name: "Large Jewel Socket".to_owned()
+                },
+            ]
+        );
+
+        // Paths do not pass through the start of another class.
+        let report = validate(&data, &tree, &scion(&[101, 201, 200]));
+        assert!(report.is_valid(), "{report}");
+        let mut allocation = scion(&[]);
+        allocation.class = 1;
+        allocation.ascendancy = 2;
+        allocation.nodes = vec![201, 100, 1200, 1201];
+        let report = validate(&data, &tree, &allocation);
+        assert!(report.is_valid(), "{report}");
+        assert_eq!(report.points, 1);
+    }
+
+    #[test]
+    fn wrong_ascendancy() {
+        let (data, tree) = trees(None);
+
+        let report = validate(&data, &tree, &scion(&[1201]));
+        assert_eq!(
+            report.violations,
+            vec![Violation::WrongAscendancy {
+                id: 1201,
+                name: "Rite of Ruin".to_owned(),
+                ascendancy: "Berserker".to_owned(),
+            }]
+        );
+
+        // Bloodlines need to be selected as well.
+        let mut allocation = allocation();
+        allocation.alternate_ascendancy = 0;
+        let report = validate(&data, &tree, &allocation);
+        assert_eq!(
+            report.violations,
+            vec![Violation::WrongAscendancy {
+                id: 1501,
+                name: "Crystalline Phylactery".to_owned(),
+                ascendancy: "Aul".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn too_many_points() {
+        let points = serde_json::json!({
+            "totalPoints": 2,
+            "ascendancyPoints": 0,
+        });
+        let (data, tree) = trees(Some(points));
+
+        let report = validate(&data, &tree, &scion(&[101, 102]));
+        assert!(report.is_valid(), "{report}");
+
+        let report = validate(&data, &tree, &scion(&[101, 102, 103, 1001]));
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::TooManyPoints {
+                    points: 3,
+                    limit: 2
+                },
+                Violation::TooManyAscendancyPoints {
+                    points: 1,
+                    limit: 0
+                },
+            ]
+        );
+        assert_eq!(report.limits.total_points, 2);
+        assert_eq!(report.notes, Vec::<String>::new());
+    }
+}